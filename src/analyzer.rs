@@ -0,0 +1,105 @@
+use std::fmt::Debug;
+
+use pathfinder_geometry::rect::RectF;
+use pdf_render::{font::OutlineBuilder, Fill, TextSpan};
+
+pub use crate::classify::{classify, Class};
+pub use crate::node::{HeaderCell, Node, NodeTag, TableCell};
+pub use table::Table;
+
+/// Splits the spans of a page into a tree of blocks, lines and cells.
+///
+/// The tree is turned into a `Flow` by the rest of the pipeline, so an analyzer only has to decide
+/// how the spans are grouped and in which order the groups are read.
+/// `Node::Final` leaves hold the indices of the spans of a line, `Node::Grid` groups them:
+/// a `NodeTag::Line` grid is read as one line, a `NodeTag::Paragraph` grid as the lines of one paragraph,
+/// and the cells of a `NodeTag::Complex` grid are read one after the other.
+pub trait LayoutAnalyzer: Debug + Send + Sync {
+    /// `boxes` holds the rectangle and index into `spans` of every span that takes part,
+    /// running headers and footers are already left out if requested.
+    /// Figures are among the boxes too, with indices from `spans.len()` on. They are obstacles
+    /// that text should not be joined across.
+    /// `rules` are the straight line segments of the vector graphics as `[x1, y1, x2, y2]`.
+    /// `fills` are the filled boxes behind text with their colour, like the shaded rows of a table,
+    /// their edges are among the `rules`.
+    fn analyze(&self, spans: &[TextSpan<OutlineBuilder>], boxes: &mut [(RectF, usize)], rules: &[[f32; 4]], fills: &[(RectF, Fill)], bbox: RectF) -> Node;
+}
+
+/// The default analyzer: finds columns, then recursively cuts at the widest horizontal or vertical gap.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct XyCut;
+
+impl LayoutAnalyzer for XyCut {
+    fn analyze(&self, spans: &[TextSpan<OutlineBuilder>], boxes: &mut [(RectF, usize)], rules: &[[f32; 4]], fills: &[(RectF, Fill)], _bbox: RectF) -> Node {
+        crate::node::xy_cut(spans, boxes, rules, fills)
+    }
+}
+
+/// Separates columns and blocks with maximal empty rectangles, the whitespace cover of Breuel.
+///
+/// Handles layouts where no gap runs through the whole page, like L-shaped layouts or text
+/// flowing around a figure. The blocks are split further with the XY cut.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WhitespaceCover;
+
+impl LayoutAnalyzer for WhitespaceCover {
+    fn analyze(&self, spans: &[TextSpan<OutlineBuilder>], boxes: &mut [(RectF, usize)], rules: &[[f32; 4]], fills: &[(RectF, Fill)], _bbox: RectF) -> Node {
+        crate::node::whitespace_cover(spans, boxes, rules, fills)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use pathfinder_geometry::vector::Vector2F;
+
+    use super::*;
+    use crate::{analyze_content, Content, ExtractOptions};
+    use crate::testing::span;
+
+    /// Reads the spans from the bottom of the page up, one paragraph each.
+    #[derive(Debug)]
+    struct BottomUp;
+
+    impl LayoutAnalyzer for BottomUp {
+        fn analyze(&self, _spans: &[TextSpan<OutlineBuilder>], boxes: &mut [(RectF, usize)], _rules: &[[f32; 4]], _fills: &[(RectF, Fill)], _bbox: RectF) -> Node {
+            boxes.sort_by(|a, b| b.0.min_y().total_cmp(&a.0.min_y()));
+            Node::Grid {
+                x: vec![],
+                y: vec![],
+                cells: boxes.iter().map(|&(_, i)| Node::Final { indices: vec![i] }).collect(),
+                tag: NodeTag::Complex,
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_analyzer() {
+        let content = || Content {
+            spans: vec![
+                span("The first paragraph.", 50.0, 100.0, 10.0),
+                span("The second paragraph.", 50.0, 200.0, 10.0),
+                span("The third paragraph.", 50.0, 300.0, 10.0),
+            ],
+            lines: vec![],
+            drawings: vec![],
+            outlines: vec![],
+            images: vec![],
+            paths: vec![],
+            fills: vec![],
+        };
+        let bbox = RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0));
+        // the second word of each line, in reading order
+        let order = |options: &ExtractOptions| {
+            let analysis = analyze_content(content(), bbox, None, None, options);
+            analysis.flow.runs.iter()
+                .flat_map(|r| r.lines.iter())
+                .map(|l| l.words[1].text.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(&ExtractOptions::default()), ["first", "second", "third"]);
+        let options = ExtractOptions { analyzer: Some(Arc::new(BottomUp)), ..Default::default() };
+        assert_eq!(order(&options), ["third", "second", "first"]);
+    }
+}
@@ -0,0 +1,193 @@
+use pathfinder_geometry::rect::RectF;
+
+use crate::flow::{Flow, RunType, Word};
+use crate::util::{avg, parse_roman};
+
+/// Words that introduce a caption, compared case insensitive and without a trailing "."
+const LABELS: &[&str] = &[
+    "figure", "fig", "table", "tab", "chart", "diagram", "exhibit", "plate", "scheme", "listing", "illustration",
+    "abbildung", "abb", "tabelle", "tableau", "tabla", "figura",
+];
+
+/// Whether the words start with a caption label like "Figure 3:" or "Table 2 –".
+///
+/// Returns `Some(true)` if the label is followed by punctuation (or nothing at all),
+/// which is enough to call it a caption even without an element next to it.
+/// "Table 2 shows …" returns `Some(false)`, it needs a table right next to it.
+pub fn parse_label(words: &[Word]) -> Option<bool> {
+    let mut words = words.iter().map(|w| w.text.as_str());
+    let name = words.next()?.trim_end_matches('.');
+    if !LABELS.iter().any(|l| l.eq_ignore_ascii_case(name)) {
+        return None;
+    }
+
+    let number = words.next()?;
+    let trimmed = number.trim_end_matches(|c| matches!(c, ':' | '.' | '–' | '—' | '-' | '|'));
+    // "3", "3.2", "S1", "A.1", "IV"
+    let numbered = trimmed.chars().any(|c| c.is_ascii_digit())
+        && trimmed.chars().all(|c| c.is_ascii_alphanumeric() || c == '.');
+    if !numbered && parse_roman(trimmed).is_none() {
+        return None;
+    }
+
+    let punctuated = trimmed.len() < number.len()
+        || words.next().map_or(true, |w| matches!(w, ":" | "–" | "—" | "-" | "|"));
+    Some(punctuated)
+}
+
+/// Merge the bounds of vector drawings and images into regions.
+///
+/// Paths that touch each other (like the rules of a table or the parts of a chart) become one region.
+/// Thin regions (single rules, underlines) and regions covering most of the page (frames, backgrounds) are dropped.
+pub fn drawing_regions(rects: &[RectF], bbox: RectF) -> Vec<RectF> {
+    let margin = 2.0;
+    let page_area = bbox.width() * bbox.height();
+    let is_background = |r: &RectF| r.width() * r.height() >= 0.9 * page_area;
+
+    let mut regions: Vec<RectF> = vec![];
+    for &rect in rects.iter().filter(|r| !is_background(r)) {
+        let mut rect = rect;
+        // absorb every region this one touches, repeat as the union may touch more
+        loop {
+            let grown = rect.dilate(margin);
+            let before = regions.len();
+            regions.retain(|r| {
+                if r.intersects(grown) {
+                    rect = rect.union_rect(*r);
+                    false
+                } else {
+                    true
+                }
+            });
+            if regions.len() == before {
+                break;
+            }
+        }
+        regions.push(rect);
+    }
+
+    regions.retain(|r| r.width() > 10.0 && r.height() > 10.0 && !is_background(r));
+    regions
+}
+
+/// Find caption runs among the paragraphs and link them to the drawing region or table right above or below them.
+pub fn link(flow: &mut Flow, regions: &[RectF]) {
+    let targets: Vec<RectF> = regions.iter().cloned()
+        .chain(flow.tables.iter().map(|t| RectF::from(t.rect)))
+        .collect();
+    for run in flow.runs.iter_mut() {
+        if run.kind != RunType::Paragraph {
+            continue;
+        }
+        let standalone = match run.lines.first().and_then(|l| parse_label(&l.words)) {
+            Some(standalone) => standalone,
+            None => continue,
+        };
+        let bbox = match run.bbox() {
+            Some(bbox) => bbox,
+            None => continue,
+        };
+        let line_height = avg(run.lines.iter().map(|l| l.rect.h)).unwrap();
+        let max_dist = 4.0 * line_height;
+
+        let target = targets.iter()
+            .filter(|r| r.min_x() < bbox.max_x() && bbox.min_x() < r.max_x())
+            .filter_map(|r| {
+                let dist = if r.max_y() <= bbox.min_y() + 0.5 * line_height {
+                    // element above the caption
+                    bbox.min_y() - r.max_y()
+                } else if r.min_y() >= bbox.max_y() - 0.5 * line_height {
+                    // element below the caption
+                    r.min_y() - bbox.max_y()
+                } else {
+                    return None;
+                };
+                (dist < max_dist).then(|| (dist, *r))
+            })
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+            .map(|(_, r)| r);
+
+        if target.is_some() || standalone {
+            run.kind = RunType::Caption { target: target.map(|r| r.into()) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{Line, Rect, Run, TableContent};
+    use crate::testing::words;
+    use pathfinder_geometry::vector::Vector2F;
+
+    fn run(text: &str, kind: RunType, y: f32) -> Run {
+        Run {
+            lines: vec![Line { words: words(text, 100.0, y, 10.0), rect: Rect { x: 100.0, y, w: 200.0, h: 10.0 } }],
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(parse_label(&words("Figure 3: Results", 0.0, 0.0, 10.0)), Some(true));
+        assert_eq!(parse_label(&words("Fig. 2.1 The setup", 0.0, 0.0, 10.0)), Some(false));
+        assert_eq!(parse_label(&words("Table IV – Costs", 0.0, 0.0, 10.0)), Some(true));
+        assert_eq!(parse_label(&words("Table 2 shows the costs", 0.0, 0.0, 10.0)), Some(false));
+        assert_eq!(parse_label(&words("Table of contents", 0.0, 0.0, 10.0)), None);
+        assert_eq!(parse_label(&words("Figures", 0.0, 0.0, 10.0)), None);
+    }
+
+    #[test]
+    fn test_link() {
+        let rect = |x0, y0, x1, y1| RectF::from_points(Vector2F::new(x0, y0), Vector2F::new(x1, y1));
+        let drawing = rect(100.0, 100.0, 300.0, 200.0);
+        let other = rect(100.0, 400.0, 300.0, 500.0);
+
+        let mut flow = Flow::new();
+        // below the drawing
+        flow.runs.push(run("Figure 1: A chart", RunType::Paragraph, 205.0));
+        // above the other one, described in a sentence
+        flow.runs.push(run("Figure 2 shows the trend", RunType::Paragraph, 385.0));
+        // far from both, but punctuated
+        flow.runs.push(run("Figure 3: Missing", RunType::Paragraph, 700.0));
+        // far from both and a sentence
+        flow.runs.push(run("Figure 4 was left out", RunType::Paragraph, 750.0));
+        // a list item stays one
+        flow.runs.push(run("Table 5: Items", RunType::ListItem { marker: "•".into(), ordinal: None, level: 0 }, 210.0));
+        // above a table without rules, which has no drawing region
+        flow.runs.push(run("Table 6: Costs", RunType::Paragraph, 585.0));
+        let table = rect(100.0, 600.0, 300.0, 650.0);
+        flow.tables.push(TableContent { table: table::Table::empty(0, 0), rect: table.into(), rows: 0, columns: 0, header_rows: 0, header: vec![] });
+        link(&mut flow, &[drawing, other]);
+
+        assert_eq!(flow.runs[0].kind, RunType::Caption { target: Some(drawing.into()) });
+        assert_eq!(flow.runs[1].kind, RunType::Caption { target: Some(other.into()) });
+        assert_eq!(flow.runs[2].kind, RunType::Caption { target: None });
+        assert_eq!(flow.runs[3].kind, RunType::Paragraph);
+        assert!(matches!(flow.runs[4].kind, RunType::ListItem { .. }));
+        assert_eq!(flow.runs[5].kind, RunType::Caption { target: Some(table.into()) });
+    }
+
+    #[test]
+    fn test_drawing_regions() {
+        let rect = |x0, y0, x1, y1| RectF::from_points(Vector2F::new(x0, y0), Vector2F::new(x1, y1));
+        let page = rect(0.0, 0.0, 600.0, 800.0);
+
+        let rects = [
+            // the rules of a table, touching each other
+            rect(100.0, 100.0, 300.0, 100.0),
+            rect(100.0, 150.0, 300.0, 150.0),
+            rect(100.0, 100.0, 100.0, 150.0),
+            rect(300.0, 100.0, 300.0, 150.0),
+            // an underline
+            rect(100.0, 400.0, 200.0, 401.0),
+            // a frame around the page
+            rect(10.0, 10.0, 590.0, 790.0),
+            // an image
+            rect(100.0, 500.0, 200.0, 600.0),
+        ];
+        let regions = drawing_regions(&rects, page);
+
+        assert_eq!(regions, vec![rect(100.0, 100.0, 300.0, 150.0), rect(100.0, 500.0, 200.0, 600.0)]);
+    }
+}
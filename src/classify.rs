@@ -3,7 +3,7 @@ use std::sync::Arc;
 use font::Encoder;
 use pdf_render::TextSpan;
 
-use crate::util::is_number;
+use crate::value::is_value;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Class {
@@ -22,7 +22,7 @@ pub fn classify<'a, E: Encoder + 'a>(spans: impl Iterator<Item=&'a TextSpan<E>>)
     let mut first_font: *const FontEntry<E> = std::ptr::null();
 
     for s in spans {
-        numeric.add(is_number(&s.text));
+        numeric.add(is_value(&s.text));
         if let Some(ref font) = s.font {
             bold.add(font.name.contains("Bold"));
             let font_ptr = Arc::as_ptr(font);
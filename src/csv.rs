@@ -0,0 +1,169 @@
+use crate::flow::{CellContent, TableContent};
+use crate::stitch::StitchedTable;
+use crate::value::Value;
+
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    /// The field separator: `,` for CSV, `\t` for TSV
+    pub delimiter: char,
+    /// Write the content of a cell spanning several rows or columns into every field it covers,
+    /// instead of only the top left one and leaving the others empty.
+    pub repeat_spanned: bool,
+    /// Start every record with the index of the page and of the table on that page.
+    pub indices: bool,
+    /// Write numbers, percentages, amounts and dates in a plain form a spreadsheet reads,
+    /// like "-1200" for "(1,200)" or "2023-01-31" for "31.01.2023", instead of the text as printed.
+    pub typed_values: bool,
+}
+impl CsvOptions {
+    pub fn csv() -> Self {
+        CsvOptions { delimiter: ',', repeat_spanned: false, indices: false, typed_values: false }
+    }
+    pub fn tsv() -> Self {
+        CsvOptions { delimiter: '\t', ..Self::csv() }
+    }
+}
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self::csv()
+    }
+}
+
+/// Write a table of page `page` as CSV or TSV, one record per row of the grid.
+///
+/// `index` is the index of the table in `Flow::tables`.
+pub fn table(table: &TableContent, page: usize, index: usize, options: &CsvOptions) -> String {
+    let cells: Vec<&CellContent> = table.table.values().map(|v| &v.value).collect();
+    write(&cells, table.rows, table.columns, |_| (page, index), options)
+}
+
+/// Write a table stitched together from several pages, every record with the page and table the row comes from.
+pub fn stitched(table: &StitchedTable, options: &CsvOptions) -> String {
+    let cells: Vec<&CellContent> = table.rows.iter().flat_map(|r| r.cells.iter()).collect();
+    write(&cells, table.rows.len(), table.columns, |r| (table.rows[r].page, table.rows[r].table), options)
+}
+
+/// CSV follows RFC 4180: fields with a delimiter, quote or line break are quoted, and records end in CRLF.
+/// TSV cannot quote, so tabs and line breaks in a field become spaces, and records end in LF.
+fn write(cells: &[&CellContent], rows: usize, columns: usize, source: impl Fn(usize) -> (usize, usize), options: &CsvOptions) -> String {
+    // every field of the grid, with whether it is the top left one of its cell
+    let mut grid: Vec<Vec<Option<(&CellContent, bool)>>> = vec![vec![None; columns]; rows];
+    for &cell in cells {
+        for r in cell.row .. (cell.row + cell.rowspan).min(rows) {
+            for c in cell.col .. (cell.col + cell.colspan).min(columns) {
+                grid[r][c] = Some((cell, r == cell.row && c == cell.col));
+            }
+        }
+    }
+
+    let tsv = options.delimiter == '\t';
+    let mut out = String::new();
+    for (r, row) in grid.iter().enumerate() {
+        let mut fields = vec![];
+        if options.indices {
+            let (page, table) = source(r);
+            fields.push(page.to_string());
+            fields.push(table.to_string());
+        }
+        for field in row {
+            fields.push(match *field {
+                Some((cell, first)) if first || options.repeat_spanned => match (&cell.value, options.typed_values) {
+                    (Some(value), true) => plain(value),
+                    _ => cell.text.clone(),
+                },
+                _ => String::new(),
+            });
+        }
+
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                out.push(options.delimiter);
+            }
+            if tsv {
+                out.extend(field.chars().map(|c| if matches!(c, '\t' | '\r' | '\n') { ' ' } else { c }));
+            } else if field.contains(|c| c == options.delimiter || matches!(c, '"' | '\r' | '\n')) {
+                out.push('"');
+                out.push_str(&field.replace('"', "\"\""));
+                out.push('"');
+            } else {
+                out.push_str(field);
+            }
+        }
+        out.push_str(if tsv { "\n" } else { "\r\n" });
+    }
+    out
+}
+
+/// The value in a form spreadsheets read. Amounts lose their currency.
+fn plain(value: &Value) -> String {
+    match *value {
+        Value::Number(n) => n.to_string(),
+        Value::Percentage(p) => format!("{}%", p),
+        Value::Currency { amount, .. } => amount.to_string(),
+        Value::Date { year, month, day } => format!("{:04}-{:02}-{:02}", year, month, day),
+        Value::Roman(n) => n.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::Rect;
+    use crate::testing;
+
+    fn cell(text: &str, row: usize, col: usize, rowspan: usize, colspan: usize) -> CellContent {
+        CellContent {
+            text: text.into(),
+            lines: vec![],
+            rect: Rect { x: 0.0, y: 0.0, w: 0.0, h: 0.0 },
+            grid_rect: Rect { x: 0.0, y: 0.0, w: 0.0, h: 0.0 },
+            value: crate::value::parse(text),
+            font_size: 10.0,
+            bold: false,
+            nested: None,
+            figures: vec![],
+            row,
+            col,
+            rowspan,
+            colspan,
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let cells = [
+            cell("Region", 0, 0, 2, 1),
+            cell("Sales, net", 0, 1, 1, 2),
+            cell("say \"hi\"", 1, 1, 1, 1),
+            cell("(1,200)", 1, 2, 1, 1),
+        ];
+        let cells: Vec<&CellContent> = cells.iter().collect();
+
+        let csv = write(&cells, 2, 3, |_| (4, 0), &CsvOptions::csv());
+        assert_eq!(csv, "Region,\"Sales, net\",\r\n,\"say \"\"hi\"\"\",\"(1,200)\"\r\n");
+
+        let options = CsvOptions { repeat_spanned: true, indices: true, typed_values: true, ..CsvOptions::tsv() };
+        let tsv = write(&cells, 2, 3, |_| (4, 0), &options);
+        assert_eq!(tsv, "4\t0\tRegion\tSales, net\tSales, net\n4\t0\tRegion\tsay \"hi\"\t-1200\n");
+    }
+
+    #[test]
+    fn test_table() {
+        let table = testing::table(&[&["Fruit", "Price", "Qty"], &["Apples", "1,200.50", "4"], &["Pears, ripe", "0.80", "12"]], 50.0, 100.0, 1);
+        let options = CsvOptions { typed_values: true, ..CsvOptions::csv() };
+        assert_eq!(super::table(&table, 0, 0, &options), "Fruit,Price,Qty\r\nApples,1200.5,4\r\n\"Pears, ripe\",0.8,12\r\n");
+        let options = CsvOptions { indices: true, ..CsvOptions::tsv() };
+        assert_eq!(super::table(&table, 3, 1, &options), "3\t1\tFruit\tPrice\tQty\n3\t1\tApples\t1,200.50\t4\n3\t1\tPears, ripe\t0.80\t12\n");
+    }
+
+    #[test]
+    fn test_page() {
+        let spans = testing::table_spans(&[["Fruit", "Price", "Qty"], ["Apples", "1,200.50", "4"], ["Pears, ripe", "0.80", "12"]], 100.0);
+        let rule = [[140.0, 110.0, 440.0, 110.0]];
+        let a = testing::analyze(testing::content(spans, &rule));
+        assert_eq!(a.flow.tables.len(), 1);
+
+        let options = CsvOptions { typed_values: true, ..CsvOptions::csv() };
+        assert_eq!(super::table(&a.flow.tables[0], 0, 0, &options), "Fruit,Price,Qty\r\nApples,1200.5,4\r\n\"Pears, ripe\",0.8,12\r\n");
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt::Write;
+
+use pathfinder_content::{outline::{ContourIterFlags, Outline}, segment::SegmentKind};
+use pathfinder_geometry::{rect::RectF, transform2d::Transform2F, vector::Vector2F};
+use pdf_render::{font::OutlineBuilder, TextChar, TextSpan};
+
+use crate::flow::{Flow, RunType};
+use crate::node::{render::render, Node};
+
+const STYLE: &str = r#"
+#drawings path { fill: none; stroke: #aaa; stroke-width: 0.5 }
+#spans rect { fill: none; stroke: #39c; stroke-width: 0.3 }
+#spans path { fill: #222 }
+#spans text { font-family: sans-serif; fill: #222 }
+#rules line { stroke: #e22; stroke-width: 1 }
+#layout rect { fill: none; stroke-width: 0.8 }
+#layout rect.Singleton { stroke: #999 }
+#layout rect.Line { stroke: #3a3 }
+#layout rect.Paragraph { stroke: #36f }
+#layout rect.Complex { stroke: #f80 }
+#layout rect.Table { stroke: #c0c }
+#layout line { stroke-width: 1.5; stroke-opacity: 0.6 }
+#layout line.gap { stroke: #f0f; stroke-width: 0.5; stroke-dasharray: 4 2 }
+#layout line.Header { stroke: #c33 }
+#layout line.Paragraph { stroke: #36f }
+#layout line.Number { stroke: #093 }
+#layout line.Mixed { stroke: #999 }
+#runs rect { fill-opacity: 0.1; stroke-opacity: 0.6; stroke-width: 0.5 }
+#runs text { font-family: sans-serif; font-size: 6px }
+"#;
+
+/// Colors for the run types, also used for their labels.
+fn run_color(kind: &RunType) -> &'static str {
+    match kind {
+        RunType::Paragraph | RunType::ParagraphContinuation => "#36f",
+        RunType::Header => "#c33",
+        RunType::Cell { .. } => "#c0c",
+        RunType::ListItem { .. } => "#093",
+        RunType::Caption { .. } => "#f80",
+        RunType::Footnote { .. } => "#960",
+        RunType::Formula { .. } => "#0aa",
+        RunType::Marginalia { .. } | RunType::Sidebar { .. } => "#777",
+        RunType::Figure { .. } => "#a5a",
+    }
+}
+
+fn run_name(kind: &RunType) -> &'static str {
+    match kind {
+        RunType::ParagraphContinuation => "ParagraphContinuation",
+        RunType::Paragraph => "Paragraph",
+        RunType::Header => "Header",
+        RunType::Cell { .. } => "Cell",
+        RunType::ListItem { .. } => "ListItem",
+        RunType::Caption { .. } => "Caption",
+        RunType::Footnote { .. } => "Footnote",
+        RunType::Formula { .. } => "Formula",
+        RunType::Marginalia { .. } => "Marginalia",
+        RunType::Sidebar { .. } => "Sidebar",
+        RunType::Figure { .. } => "Figure",
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn write_outline(w: &mut String, outline: &Outline) {
+    let p = |v: Vector2F| format!("{} {}", v.x(), v.y());
+    w.push_str(r#"<path d=""#);
+    for contour in outline.contours() {
+        let mut first = true;
+        for segment in contour.iter(ContourIterFlags::empty()) {
+            if first {
+                write!(w, "M{} ", p(segment.baseline.from())).unwrap();
+                first = false;
+            }
+            match segment.kind {
+                SegmentKind::None => {}
+                SegmentKind::Line => write!(w, "L{} ", p(segment.baseline.to())).unwrap(),
+                SegmentKind::Quadratic => write!(w, "Q{} {} ", p(segment.ctrl.from()), p(segment.baseline.to())).unwrap(),
+                SegmentKind::Cubic => write!(w, "C{} {} {} ", p(segment.ctrl.from()), p(segment.ctrl.to()), p(segment.baseline.to())).unwrap(),
+            }
+        }
+    }
+    w.push_str(r#"" />"#);
+    w.push('\n');
+}
+
+/// The outline of the glyph for the char `c` with the text `s`, placed on the page.
+///
+/// The traced chars do not keep their glyph ids, so the glyph is looked up by the unicode of the text.
+/// The unicode of a CID font does not lead back to its glyphs reliably, their chars are written as text.
+fn glyph_outline(span: &TextSpan<OutlineBuilder>, c: &TextChar, s: &str) -> Option<Outline> {
+    let entry = span.font.as_ref().filter(|f| !f.is_cid)?;
+    let font = &entry.font;
+    let gid = font.gid_for_unicode_codepoint(s.chars().next()? as u32)?;
+    let glyph = font.glyph(gid)?;
+    let transform = span.transform
+        * Transform2F::from_translation(Vector2F::new(c.pos, 0.0))
+        * Transform2F::from_scale(span.font_size)
+        * font.font_matrix();
+    Some(glyph.shape.transformed(&transform))
+}
+
+/// Render the analysis of a page as a standalone SVG document, in layers:
+///
+/// - `drawings`: the outlines of the vector graphics
+/// - `spans`: the box of each text span and the outlines of its glyphs, placed at the traced char positions
+/// - `rules`: the horizontal and vertical ruling lines that were drawn
+/// - `layout`: the layout tree, node boxes colored by tag, the gaps between cells dashed,
+///   and the lines of the leaves colored by their class
+/// - `runs`: the runs of the flow, colored and labeled by their type
+pub fn svg(spans: &[TextSpan<OutlineBuilder>], root: &Node, flow: &Flow, bbox: RectF, rules: &[[f32; 4]], drawings: &[Outline]) -> String {
+    let mut w = String::new();
+    writeln!(w, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        bbox.min_x(), bbox.min_y(), bbox.width(), bbox.height(), bbox.width(), bbox.height()
+    ).unwrap();
+    writeln!(w, "<style>{}</style>", STYLE).unwrap();
+
+    w.push_str("<g id=\"drawings\">\n");
+    for outline in drawings {
+        write_outline(&mut w, outline);
+    }
+    w.push_str("</g>\n");
+
+    w.push_str("<g id=\"spans\">\n");
+    for (i, span) in spans.iter().enumerate() {
+        let r = span.rect;
+        writeln!(w, r#"<rect x="{}" y="{}" width="{}" height="{}" data-span="{}" />"#,
+            r.min_x(), r.min_y(), r.width(), r.height(), i
+        ).unwrap();
+
+        // the glyphs are drawn from the outlines of the font, text is only written for chars without one
+        let baseline = span.transform.vector.y();
+        let mut x = String::new();
+        let mut text = String::new();
+        for (j, c) in span.chars.iter().enumerate() {
+            let end = span.chars.get(j + 1).map_or(span.text.len(), |next| next.offset);
+            let s = &span.text[c.offset..end];
+            if s.trim().is_empty() {
+                continue;
+            }
+            match glyph_outline(span, c, s) {
+                Some(outline) => write_outline(&mut w, &outline),
+                None => {
+                    // one position per char of the text
+                    for _ in s.chars() {
+                        write!(x, "{} ", (span.transform * Vector2F::new(c.pos, 0.0)).x()).unwrap();
+                    }
+                    text.push_str(s);
+                }
+            }
+        }
+        if !text.is_empty() {
+            writeln!(w, r#"<text x="{}" y="{}" font-size="{}">{}</text>"#,
+                x.trim_end(), baseline, r.height(), escape(&text)
+            ).unwrap();
+        }
+    }
+    w.push_str("</g>\n");
+
+    w.push_str("<g id=\"rules\">\n");
+    for &[x1, y1, x2, y2] in rules.iter().filter(|&&[x1, y1, x2, y2]| x1 == x2 || y1 == y2) {
+        writeln!(w, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" />"#, x1, y1, x2, y2).unwrap();
+    }
+    w.push_str("</g>\n");
+
+    w.push_str("<g id=\"layout\">\n");
+    render(&mut w, spans, root, bbox);
+    w.push_str("\n</g>\n");
+
+    w.push_str("<g id=\"runs\">\n");
+    for (i, run) in flow.runs.iter().enumerate() {
+        if let Some(r) = run.bbox() {
+            let color = run_color(&run.kind);
+            writeln!(w, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{color}" stroke="{color}" data-run="{}" />"#,
+                r.min_x(), r.min_y(), r.width(), r.height(), i
+            ).unwrap();
+            writeln!(w, r#"<text x="{}" y="{}" fill="{color}">{} {}</text>"#,
+                r.min_x(), r.min_y() - 1.0, i, run_name(&run.kind)
+            ).unwrap();
+        }
+    }
+    w.push_str("</g>\n");
+
+    w.push_str("</svg>\n");
+    w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::span;
+    use pathfinder_geometry::vector::Vector2F;
+
+    /// The names of the elements, checking that each is closed in the right order.
+    fn elements(svg: &str) -> Vec<&str> {
+        let mut open = vec![];
+        let mut names = vec![];
+        for tag in svg.split('<').skip(1).map(|t| &t[..t.find('>').unwrap()]) {
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name));
+                continue;
+            }
+            let name = tag.split_whitespace().next().unwrap();
+            names.push(name);
+            if !tag.ends_with('/') {
+                open.push(name);
+            }
+        }
+        assert!(open.is_empty(), "{:?} not closed", open);
+        names
+    }
+
+    #[test]
+    fn test_svg() {
+        let spans = [span("Tom & Jerry", 50.0, 110.0, 10.0), span("<b>", 50.0, 125.0, 10.0)];
+        let root = Node::Grid {
+            x: vec![],
+            y: vec![112.5],
+            cells: vec![Node::Final { indices: vec![0] }, Node::Final { indices: vec![1] }],
+            tag: crate::node::NodeTag::Paragraph,
+        };
+        let bbox = RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0));
+        let svg = svg(&spans, &root, &Flow::new(), bbox, &[[50.0, 130.0, 200.0, 130.0]], &[]);
+
+        assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>\n"));
+        elements(&svg);
+        let layer = |id: &str| {
+            let start = svg.find(&format!("<g id=\"{}\">", id)).unwrap();
+            &svg[start .. start + svg[start..].find("</g>").unwrap() + 4]
+        };
+        // a box and the text for each span, the fonts have no outlines to draw
+        let spans_layer = elements(layer("spans"));
+        assert_eq!(spans_layer.iter().filter(|&&e| e == "rect").count(), spans.len());
+        assert_eq!(spans_layer.iter().filter(|&&e| e == "text").count(), spans.len());
+        // each char is placed on its own, without the spaces
+        assert!(layer("spans").contains(">Tom&amp;Jerry</text>"));
+        assert!(layer("spans").contains(">&lt;b&gt;</text>"));
+        assert_eq!(elements(layer("rules")), ["g", "line"]);
+    }
+}
@@ -0,0 +1,96 @@
+use font::Encoder;
+use pdf_render::{TextChar, TextSpan};
+
+use crate::util::{median_span, remove_indices};
+
+/// Find drop caps and merge them into the first word of their paragraph.
+///
+/// A drop cap is a span of one or two letters at least two and a half times the height of the body text,
+/// with at least two lines of text right of it, the first of them at about its top.
+/// Its text is put in front of the first span of that first line, and the span is removed,
+/// so it neither becomes a header of its own nor leaves the first word without its first letter.
+pub fn merge<E: Encoder>(spans: &mut Vec<TextSpan<E>>) {
+    let body = match median_span(spans.iter()).map(|s| s.rect.height()) {
+        Some(h) if h > 0.0 => h,
+        _ => return,
+    };
+
+    let mut merged = vec![];
+    for cap in 0..spans.len() {
+        let rect = spans[cap].rect;
+        let text = spans[cap].text.trim();
+        let is_cap = matches!(text.chars().count(), 1 | 2)
+            && text.chars().last().map_or(false, |c| c.is_alphabetic())
+            && rect.height() >= 2.5 * body;
+        if !is_cap {
+            continue;
+        }
+
+        // the spans of body text right of the drop cap
+        let next_to: Vec<usize> = (0..spans.len())
+            .filter(|&i| i != cap && !merged.contains(&i))
+            .filter(|&i| {
+                let r = spans[i].rect;
+                r.height() < 1.5 * body
+                    && r.min_x() >= rect.max_x() - 0.5 * body && r.min_x() < rect.max_x() + 2.0 * body
+                    && r.center().y() > rect.min_y() && r.center().y() < rect.max_y() + 0.5 * body
+            })
+            .collect();
+
+        let mut rows: Vec<f32> = next_to.iter().map(|&i| spans[i].rect.center().y()).collect();
+        rows.sort_by(f32::total_cmp);
+        rows.dedup_by(|a, b| (*a - *b).abs() < 0.5 * body);
+        if rows.len() < 2 {
+            continue;
+        }
+
+        let first = next_to.iter().cloned()
+            .filter(|&i| (spans[i].rect.center().y() - rows[0]).abs() < 0.5 * body)
+            .min_by(|&a, &b| spans[a].rect.min_x().partial_cmp(&spans[b].rect.min_x()).unwrap());
+        let first = match first {
+            Some(i) if (spans[i].rect.min_y() - rect.min_y()).abs() < 1.5 * body => i,
+            _ => continue,
+        };
+
+        let cap_text = text.to_string();
+        let target = &mut spans[first];
+        let pos = target.chars.first().map_or(0.0, |c| c.pos);
+        for c in target.chars.iter_mut() {
+            c.offset += cap_text.len();
+        }
+        let cap_chars = cap_text.char_indices().map(|(offset, _)| TextChar { offset, pos, width: 0.0 });
+        target.chars.splice(0..0, cap_chars);
+        target.text.insert_str(0, &cap_text);
+        merged.push(cap);
+    }
+
+    remove_indices(spans, &merged);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::span;
+
+    #[test]
+    fn test_merge() {
+        let mut spans = vec![
+            span("O", 50.0, 136.0, 36.0),
+            span("nce upon a time", 72.0, 110.0, 10.0),
+            span("there was a drop cap", 72.0, 122.0, 10.0),
+            span("next to three lines", 72.0, 134.0, 10.0),
+        ];
+        merge(&mut spans);
+        assert_eq!(spans.len(), 3);
+        assert_eq!(spans[0].text, "Once upon a time");
+        assert_eq!(spans[0].chars[1].offset, 1);
+
+        // a big letter alone is left alone
+        let mut spans = vec![
+            span("A", 50.0, 136.0, 36.0),
+            span("side note", 300.0, 110.0, 10.0),
+        ];
+        merge(&mut spans);
+        assert_eq!(spans.len(), 2);
+    }
+}
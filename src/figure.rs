@@ -0,0 +1,204 @@
+use font::Encoder;
+use pathfinder_geometry::rect::RectF;
+use pdf_render::TextSpan;
+
+use crate::caption::drawing_regions;
+use crate::flow::{Line, Run, RunType};
+use crate::math::rows;
+use crate::text::concat_text;
+
+/// An image on the page, or a cluster of vector graphics like a chart or a diagram.
+///
+/// Figures take part in the layout analysis as boxes of their own, with the indices
+/// after those of the spans: figure `k` has index `spans.len() + k`.
+#[derive(Debug, Clone)]
+pub struct Figure {
+    pub rect: RectF,
+    /// Width and height of the image in pixels
+    pub image_size: Option<(u32, u32)>,
+    /// The spans on top of the figure, like the labels of a chart
+    pub spans: Vec<usize>,
+}
+
+/// Find the figures of the page.
+///
+/// `images` are the placements of images with their size in pixels, `paths` the bounds of the vector paths
+/// and whether the path is only made of horizontal and vertical lines, like the rules of a table.
+/// Tiny images (icons, bullets) are ignored. Vector paths make a figure if at least eight paths
+/// with curves or slanted lines touch each other.
+/// Text on top of a figure belongs to it, unless there is so much of it that the figure is rather a background.
+pub fn detect<E: Encoder>(images: &[(RectF, Option<(u32, u32)>)], paths: &[(RectF, bool)], spans: &[TextSpan<E>], bbox: RectF) -> Vec<Figure> {
+    let page_area = bbox.width() * bbox.height();
+    let area = |r: &RectF| r.width() * r.height();
+
+    let mut figures: Vec<Figure> = images.iter()
+        .filter(|(r, _)| r.width() > 10.0 && r.height() > 10.0 && area(r) > 0.002 * page_area && area(r) < 0.9 * page_area)
+        .map(|&(rect, image_size)| Figure { rect, image_size, spans: vec![] })
+        .collect();
+
+    let drawn: Vec<RectF> = paths.iter().filter(|(_, ruling)| !ruling).map(|&(r, _)| r).collect();
+    for region in drawing_regions(&drawn, bbox) {
+        let grown = region.dilate(2.0);
+        if drawn.iter().filter(|r| grown.contains_rect(**r)).count() >= 8 {
+            figures.push(Figure { rect: region, image_size: None, spans: vec![] });
+        }
+    }
+
+    // merge overlapping figures, like an image with a drawing on top
+    let mut merged: Vec<Figure> = vec![];
+    for mut figure in figures {
+        loop {
+            let before = merged.len();
+            merged.retain(|f| {
+                if f.rect.intersects(figure.rect) {
+                    figure.rect = figure.rect.union_rect(f.rect);
+                    figure.image_size = figure.image_size.or(f.image_size);
+                    false
+                } else {
+                    true
+                }
+            });
+            if merged.len() == before {
+                break;
+            }
+        }
+        merged.push(figure);
+    }
+
+    for figure in merged.iter_mut() {
+        figure.spans = (0..spans.len())
+            .filter(|&i| figure.rect.contains_point(spans[i].rect.center()))
+            .collect();
+    }
+    merged.retain(|f| {
+        let text_area: f32 = f.spans.iter().map(|&i| area(&spans[i].rect)).sum();
+        text_area < 0.4 * area(&f.rect)
+    });
+    merged
+}
+
+/// A `RunType::Figure` run, the text on the figure in lines.
+pub fn figure_run<E: Encoder>(figure: &Figure, spans: &[TextSpan<E>]) -> Run {
+    let figure_spans: Vec<&TextSpan<E>> = figure.spans.iter().filter_map(|&i| spans.get(i)).collect();
+    let mut text = String::new();
+    let lines = rows(&figure_spans).into_iter()
+        .map(|row| {
+            let rect = row.iter().map(|s| s.rect).reduce(|a, b| a.union_rect(b)).unwrap();
+            let words = concat_text(&mut text, row.iter().cloned());
+            text.push('\n');
+            Line { words, rect: rect.into() }
+        })
+        .collect();
+
+    Run {
+        lines,
+        kind: RunType::Figure {
+            rect: figure.rect.into(),
+            image_size: figure.image_size,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{build, Flow};
+    use crate::node::{Node, NodeTag, TableCell};
+    use crate::testing::span;
+    use pathfinder_geometry::vector::Vector2F;
+    use pdf_render::font::OutlineBuilder;
+
+    fn page() -> RectF {
+        RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0))
+    }
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> RectF {
+        RectF::new(Vector2F::new(x, y), Vector2F::new(w, h))
+    }
+
+    #[test]
+    fn test_drawing() {
+        // the curves of a chart touching each other, with a label on top and body text below
+        let paths: Vec<(RectF, bool)> = (0..8).map(|k| (rect(50.0 + 25.0 * k as f32, 100.0, 30.0, 60.0), false)).collect();
+        let spans = [span("Q1", 60.0, 120.0, 10.0), span("The text below the chart.", 50.0, 400.0, 10.0)];
+        let figures = detect(&[], &paths, &spans, page());
+        assert_eq!(figures.len(), 1);
+        assert_eq!(figures[0].rect, rect(50.0, 100.0, 205.0, 60.0));
+        assert_eq!(figures[0].spans, [0]);
+        assert_eq!(figures[0].image_size, None);
+
+        // too few curves, or only rules like a table frame
+        assert!(detect(&[], &paths[..4], &spans, page()).is_empty());
+        let rules: Vec<(RectF, bool)> = paths.iter().map(|&(r, _)| (r, true)).collect();
+        assert!(detect(&[], &rules, &spans, page()).is_empty());
+    }
+
+    #[test]
+    fn test_box_of_text() {
+        // a box drawn around a paragraph is a background, not a figure
+        let paths: Vec<(RectF, bool)> = (0..8).map(|k| (rect(50.0 + 25.0 * k as f32, 100.0, 30.0, 90.0), false)).collect();
+        let spans: Vec<_> = (0..8).map(|k| span("A line of text in a box wide", 55.0, 110.0 + 10.0 * k as f32, 10.0)).collect();
+        assert!(detect(&[], &paths, &spans, page()).is_empty());
+    }
+
+    #[test]
+    fn test_image() {
+        let images = [(rect(100.0, 500.0, 200.0, 100.0), Some((800, 400))), (rect(50.0, 50.0, 8.0, 8.0), Some((16, 16)))];
+        let figures = detect::<OutlineBuilder>(&images, &[], &[], page());
+        assert_eq!(figures.len(), 1);
+        assert_eq!(figures[0].rect, images[0].0);
+        assert_eq!(figures[0].image_size, Some((800, 400)));
+        assert!(figures[0].spans.is_empty());
+    }
+
+    #[test]
+    fn test_figure_in_paragraph() {
+        // a paragraph interrupted by an image, which the layout put in a line of its own
+        let spans = [
+            span("The text above", 50.0, 110.0, 10.0), span("the image and", 50.0, 122.0, 10.0),
+            span("the text below", 50.0, 250.0, 10.0), span("the image.", 50.0, 262.0, 10.0),
+        ];
+        let figures = [Figure { rect: rect(50.0, 130.0, 200.0, 100.0), image_size: Some((400, 200)), spans: vec![] }];
+        let cells = [0, 1, 4, 2, 3].iter().map(|&i| Node::Final { indices: vec![i] }).collect();
+        let root = Node::Grid { x: vec![], y: vec![], cells, tag: NodeTag::Paragraph };
+        let mut flow = Flow::new();
+        build(&mut flow, &spans, &root, 50.0, &[], &figures);
+
+        let kinds: Vec<_> = flow.runs.iter().map(|r| (&r.kind, r.lines.len())).collect();
+        assert_eq!(kinds, [
+            (&RunType::Paragraph, 2),
+            (&RunType::Figure { rect: figures[0].rect.into(), image_size: Some((400, 200)) }, 0),
+            (&RunType::Paragraph, 2),
+        ]);
+    }
+
+    #[test]
+    fn test_figure_in_table() {
+        // a logo next to a name in a table, and a cell with nothing but a chart
+        let spans = [span("ACME", 100.0, 110.0, 10.0), span("Sales", 50.0, 110.0, 10.0)];
+        let figures = [
+            Figure { rect: rect(130.0, 100.0, 10.0, 10.0), image_size: None, spans: vec![] },
+            Figure { rect: rect(50.0, 120.0, 80.0, 40.0), image_size: None, spans: vec![] },
+        ];
+        let cell = |row, col, indices| TableCell { row, col, rowspan: 1, colspan: 1, rect: RectF::default(), indices, nested: None };
+        let mut table = table::Table::empty(2, 2);
+        for cell in [cell(0, 0, vec![1]), cell(0, 1, vec![0, 2]), cell(1, 0, vec![3])] {
+            let (row, col) = (cell.row as u32, cell.col as u32);
+            table.set_cell(cell, row, col, 1, 1);
+        }
+        let mut flow = Flow::new();
+        build(&mut flow, &spans, &Node::Table { table, header_rows: 0, header: vec![] }, 50.0, &[], &figures);
+
+        let table = &flow.tables[0].table;
+        let cells: Vec<_> = table.values().map(|v| (v.value.text.as_str(), v.value.figures.clone())).collect();
+        assert_eq!(cells, [
+            ("Sales", vec![]),
+            ("ACME", vec![figures[0].rect.into()]),
+            ("", vec![figures[1].rect.into()]),
+        ]);
+        assert_eq!(table.values().nth(2).unwrap().value.rect, figures[1].rect.into());
+        // the cells with text are cell runs as well
+        let runs: Vec<_> = flow.runs.iter().map(|r| r.kind.clone()).collect();
+        assert_eq!(runs, [RunType::Cell { table: 0, row: 0, col: 0 }, RunType::Cell { table: 0, row: 0, col: 1 }]);
+    }
+}
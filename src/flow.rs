@@ -1,9 +1,14 @@
 use crate::classify::{classify, Class};
-use crate::node::{Node, NodeTag};
+use crate::list;
+use crate::math;
+use crate::figure::{self, Figure};
+use crate::page_number::PageNumber;
+use crate::node::{Node, NodeTag, TableCell};
 use crate::util::avg;
 use crate::text::concat_text;
+use crate::value::{self, Value};
 use std::iter::once;
-use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::{rect::RectF, vector::Vector2F};
 use pdf_render::TextSpan;
 
 use std::mem::take;
@@ -11,42 +16,167 @@ use font::Encoder;
 use serde::{Serialize, Deserialize};
 use table::Table;
 
-#[derive(Serialize, Deserialize)]
+pub use crate::node::HeaderCell;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Word {
     pub text: String,
     pub rect: Rect,
-    pub chars: Vec<Char>
+    pub chars: Vec<Char>,
+    /// The number, currency, percentage or date this word represents, if any
+    pub value: Option<Value>,
+    /// Position relative to the baseline of the line, `Baseline` if the chars are mixed
+    pub position: VerticalPosition,
+}
+impl Word {
+    /// The text of each char.
+    pub fn char_texts(&self) -> impl Iterator<Item=&str> + '_ {
+        let ends = self.chars.iter().skip(1).map(|c| c.offset).chain(std::iter::once(self.text.len()));
+        self.chars.iter().zip(ends).map(move |(c, end)| &self.text[c.offset..end])
+    }
+
+    /// The text with superscripts written as "^6" or "^{12}" and subscripts as "_2" or "_{ij}".
+    pub fn marked_text(&self) -> String {
+        let mut out = String::new();
+        let mut chars = self.chars.iter().map(|c| c.position).zip(self.char_texts()).peekable();
+        while let Some((position, text)) = chars.next() {
+            let marker = match position {
+                VerticalPosition::Baseline => {
+                    out.push_str(text);
+                    continue;
+                }
+                VerticalPosition::Superscript => '^',
+                VerticalPosition::Subscript => '_',
+            };
+            let mut group = String::from(text);
+            while let Some((_, text)) = chars.next_if(|&(p, _)| p == position) {
+                group.push_str(text);
+            }
+            out.push(marker);
+            if group.chars().count() > 1 {
+                out.push('{');
+                out.push_str(&group);
+                out.push('}');
+            } else {
+                out.push_str(&group);
+            }
+        }
+        out
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VerticalPosition {
+    Baseline,
+    /// Raised and smaller than the line, like exponents and footnote markers
+    Superscript,
+    /// Lowered and smaller than the line, like chemical formulas
+    Subscript,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Char {
     // Byte offset
     pub offset: usize,
     pub pos: f32,
     pub width: f32,
+    pub position: VerticalPosition,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Line {
     pub words: Vec<Word>,
     pub rect: Rect,
 }
+impl Line {
+    /// The words separated by spaces. With `marked` superscripts and subscripts are marked, see [`Word::marked_text`].
+    pub fn text(&self, marked: bool) -> String {
+        let mut out = String::new();
+        for (i, word) in self.words.iter().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            if marked {
+                out.push_str(&word.marked_text());
+            } else {
+                out.push_str(&word.text);
+            }
+        }
+        out
+    }
+}
 #[derive(Serialize, Deserialize)]
 pub struct Run {
     pub lines: Vec<Line>,
     pub kind: RunType,
 }
+impl Run {
+    /// The lines separated by newlines.
+    pub fn text(&self, marked: bool) -> String {
+        self.lines.iter().map(|l| l.text(marked)).collect::<Vec<_>>().join("\n")
+    }
 
-#[derive(Serialize, Deserialize)]
+    /// The union of the line rectangles.
+    pub(crate) fn bbox(&self) -> Option<RectF> {
+        self.lines.iter()
+            .map(|l| RectF::from(l.rect))
+            .reduce(|a, b| a.union_rect(b))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum RunType {
     ParagraphContinuation,
     Paragraph,
     Header,
-    Cell,
+    /// A table cell, also found in `Flow::tables`
+    Cell {
+        /// Index of the table in `Flow::tables`
+        table: usize,
+        row: usize,
+        col: usize,
+    },
+    ListItem {
+        /// The bullet or number as printed, e.g. "•" or "(iv)"
+        marker: String,
+        /// The position of a numbered item in its list
+        ordinal: Option<u32>,
+        /// Nesting depth, 0 for the outermost list
+        level: usize,
+    },
+    Caption {
+        /// The bounding box of the figure, drawing or table being captioned
+        target: Option<Rect>,
+    },
+    Footnote {
+        /// The marker that refers to this footnote, e.g. "3" or "*"
+        label: Option<String>,
+    },
+    Formula {
+        rect: Rect,
+        /// Best effort linear form, like "(a + b)/(2)"
+        text: String,
+    },
+    /// A note in the side margin
+    Marginalia {
+        /// Index of the body run next to it
+        anchor: Option<usize>,
+    },
+    /// A longer text block in the side margin, like a box in a textbook
+    Sidebar {
+        /// Index of the body run next to it
+        anchor: Option<usize>,
+    },
+    /// An image or drawing, the lines hold the text on top of it
+    Figure {
+        rect: Rect,
+        /// Width and height of the image in pixels
+        image_size: Option<(u32, u32)>,
+    },
 }
 
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[derive(Serialize, Deserialize)]
 #[repr(C)]
 pub struct Rect {
@@ -65,24 +195,93 @@ impl From<RectF> for Rect {
         }
     }
 }
+impl From<Rect> for RectF {
+    fn from(r: Rect) -> Self {
+        RectF::new(Vector2F::new(r.x, r.y), Vector2F::new(r.w, r.h))
+    }
+}
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CellContent {
+    /// The lines of the cell joined by spaces
     pub text: String,
+    pub lines: Vec<Line>,
+    /// The bounds of the text
     pub rect: Rect,
+    /// The bounds of the cell in the grid of the table
+    pub grid_rect: Rect,
+    pub value: Option<Value>,
+    /// The average font size of the text
+    pub font_size: f32,
+    /// Whether all of the text is set in a bold font
+    pub bold: bool,
+    /// The bounds of the images and drawings in the cell, like a logo or a small chart
+    pub figures: Vec<Rect>,
+    /// A table inside the cell, like a small grid in a cell of a form. Its text is in the lines of the cell as well.
+    pub nested: Option<Box<TableContent>>,
+    /// Row and column of the top left corner of the cell
+    pub row: usize,
+    pub col: usize,
+    pub rowspan: usize,
+    pub colspan: usize,
+}
+
+/// A table found on the page.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableContent {
+    pub table: Table<CellContent>,
+    pub rect: Rect,
+    /// The size of the grid
+    pub rows: usize,
+    pub columns: usize,
+    /// The number of rows at the top of `table` that form its header
+    pub header_rows: usize,
+    /// The header cells as a tree: a cell spanning several columns, like "Size" over "min" and "max",
+    /// has the header cells below it as children.
+    pub header: Vec<HeaderCell<CellContent>>,
+}
+
+/// A footnote marker in the body text.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FootnoteRef {
+    pub label: String,
+    /// Index of the run, line and word that carries the marker
+    pub run: usize,
+    pub line: usize,
+    pub word: usize,
+    /// Index of the `RunType::Footnote` run with the same label, `None` if footnotes were left out
+    pub footnote: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Flow {
     pub runs: Vec<Run>,
+    pub footnote_refs: Vec<FootnoteRef>,
+    pub tables: Vec<TableContent>,
+    /// The page number printed on the page
+    pub page_number: Option<PageNumber>,
+    /// The label of the page from the catalog's `/PageLabels`
+    pub page_label: Option<String>,
+    /// The direction the text on the page runs in, in degrees clockwise (0, 90, 180 or 270).
+    /// The page was turned back by this much, so that the text reads upright.
+    pub rotation: u32,
 }
 
 impl Flow {
     pub fn new() -> Self {
         Flow { 
-            runs: vec![]
+            runs: vec![],
+            footnote_refs: vec![],
+            tables: vec![],
+            page_number: None,
+            page_label: None,
+            rotation: 0,
         }
     }
+    /// The runs separated by blank lines.
+    pub fn text(&self, marked: bool) -> String {
+        self.runs.iter().map(|r| r.text(marked)).collect::<Vec<_>>().join("\n\n")
+    }
     pub fn add_line(&mut self, words: Vec<Word>, kind: RunType, rect: Rect) {
         if words.len() > 0 {
             self.runs.push(Run {
@@ -91,14 +290,73 @@ impl Flow {
             });
         }
     }
-    pub fn add_table(&mut self, table: Table<CellContent>) {
-        
+    pub fn add_table(&mut self, table: TableContent) {
+        self.tables.push(table);
+    }
+}
+
+fn run_type(class: Class, words: &[Word]) -> RunType {
+    match class {
+        Class::Header => RunType::Header,
+        _ => list::line_marker(words).map(RunType::from).unwrap_or(RunType::Paragraph),
+    }
+}
+
+/// Split the lines of a paragraph node into runs, either list items or paragraphs separated by indentation.
+fn paragraph_runs(para_lines: Vec<(RectF, Vec<Word>)>, class: Class, left_margin: f32, indent: bool, line_height: f32) -> Vec<Run> {
+    if para_lines.is_empty() {
+        return vec![];
+    }
+    let kind = match class {
+        Class::Header => RunType::Header,
+        _ => RunType::Paragraph
+    };
+
+    // A list keeps one run per item, the indentation heuristic below would split
+    // items with hanging indents into one run per line.
+    if class != Class::Header && list::is_list(&para_lines, line_height) {
+        return list::split_items(para_lines, kind, line_height);
+    }
+
+    let mut runs = vec![];
+    let mut flow_lines = vec![];
+    for (i, (line_bbox, words)) in para_lines.into_iter().enumerate() {
+        // if a line is indented(indent = true) or outdented(indent = false), it marks a new paragraph
+        // so here, save previous lines as a new run.
+        if i != 0 && (line_bbox.min_x() >= left_margin) == indent {
+            runs.push(Run {
+                lines: take(&mut flow_lines),
+                kind: kind.clone(),
+            });
+        }
+        if words.len() > 0 {
+            flow_lines.push(Line { words , rect: line_bbox.into()});
+        }
     }
+
+    runs.push(Run {
+        lines: flow_lines,
+        kind,
+    });
+    runs
 }
 
-pub(crate) fn build<E: Encoder>(mut flow: &mut Flow, spans: &[TextSpan<E>], node: &Node, x_anchor: f32) {
+/// Split the figures off the indices of a leaf, see `Figure`.
+/// Returns the figures above the text, the indices of the spans and the figures below the text.
+fn split_figures<'a, E: Encoder>(indices: &[usize], spans: &[TextSpan<E>], figures: &'a [Figure]) -> (Vec<&'a Figure>, Vec<usize>, Vec<&'a Figure>) {
+    let (figure_indices, text): (Vec<usize>, Vec<usize>) = indices.iter().partition(|&&i| i >= spans.len());
+    let text_top = text.iter().flat_map(|&i| spans.get(i)).map(|s| s.rect.min_y()).reduce(f32::min);
+    let (above, below) = figure_indices.iter()
+        .map(|&i| &figures[i - spans.len()])
+        .partition(|f| text_top.map_or(true, |y| f.rect.min_y() < y));
+    (above, text, below)
+}
+
+pub(crate) fn build<E: Encoder>(mut flow: &mut Flow, spans: &[TextSpan<E>], node: &Node, x_anchor: f32, rules: &[[f32; 4]], figures: &[Figure]) {
     match *node {
         Node::Final { ref indices } => {
+            let (above, indices, below) = split_figures(indices, spans, figures);
+            flow.runs.extend(above.into_iter().map(|f| figure::figure_run(f, spans)));
             if indices.len() > 0 {
                 let node_spans = indices.iter()
                     .flat_map(|&i| spans.get(i));
@@ -106,18 +364,22 @@ pub(crate) fn build<E: Encoder>(mut flow: &mut Flow, spans: &[TextSpan<E>], node
                     .map(|s| s.rect)
                     .reduce(|a, b| a.union_rect(b))
                     .unwrap();
+
+                let formula: Vec<_> = node_spans.clone().collect();
+                if math::is_formula(&formula, rules) {
+                    flow.runs.push(math::formula_run(&formula, rules));
+                    return;
+                }
                 
                 let class = classify(node_spans.clone());
                 let mut text = String::new();
                 let words = concat_text(&mut text, node_spans);
 
-                let t = match class {
-                    Class::Header => RunType::Header,
-                    _ => RunType::Paragraph,
-                };
+                let t = run_type(class, &words);
               
                 flow.add_line(words, t, bbox.into());
             }
+            flow.runs.extend(below.into_iter().map(|f| figure::figure_run(f, spans)));
         }
         Node::Grid { ref x, ref y, ref cells, tag } => {
             match tag {
@@ -125,41 +387,60 @@ pub(crate) fn build<E: Encoder>(mut flow: &mut Flow, spans: &[TextSpan<E>], node
                 NodeTag::Line => {
                     let mut indices = vec![];
                     node.indices(&mut indices);
+                    let (above, indices, below) = split_figures(&indices, spans, figures);
+                    flow.runs.extend(above.into_iter().map(|f| figure::figure_run(f, spans)));
 
-                    let line_spans = indices.iter().flat_map(|&i| spans.get(i));
-                    let bbox: RectF = line_spans.clone().map(|s| s.rect).reduce(|a, b| a.union_rect(b)).unwrap().into();
+                    if indices.len() > 0 {
+                        let line_spans = indices.iter().flat_map(|&i| spans.get(i));
+                        let bbox: RectF = line_spans.clone().map(|s| s.rect).reduce(|a, b| a.union_rect(b)).unwrap().into();
 
-                    let class = classify(line_spans.clone());
-                    let mut text = String::new();
-                    let words = concat_text(&mut text, line_spans);
+                        let formula: Vec<_> = line_spans.clone().collect();
+                        if math::is_formula(&formula, rules) {
+                            flow.runs.push(math::formula_run(&formula, rules));
+                        } else {
+                            let class = classify(line_spans.clone());
+                            let mut text = String::new();
+                            let words = concat_text(&mut text, line_spans);
 
-                    let t = match class {
-                        Class::Header => RunType::Header,
-                        _ => RunType::Paragraph,
-                    };
-                
-                    flow.add_line(words, t, bbox.into());
+                            let t = run_type(class, &words);
+
+                            flow.add_line(words, t, bbox.into());
+                        }
+                    }
+                    flow.runs.extend(below.into_iter().map(|f| figure::figure_run(f, spans)));
                 }
                 NodeTag::Paragraph => {
                     assert_eq!(x.len(), 0, "For paragraph x gaps must be empty");
 
-                    let mut lines: Vec<(RectF, usize)> = vec![];
+                    // the lines with the figures above and below them
+                    let mut lines: Vec<(RectF, usize, Vec<&Figure>, Vec<&Figure>)> = vec![];
                     let mut indices = vec![];
 
                     for n in cells {
-                        let start: usize = indices.len();
-                        n.indices(&mut indices);
-                        if indices.len() > start {
-                            let cell_spans = indices[start..].iter().flat_map(|&i| spans.get(i));
-                            let bbox = cell_spans.map(|s| s.rect).reduce(|a, b| a.union_rect(b)).unwrap().into();
-                            lines.push((bbox, indices.len()));
+                        let mut cell_indices = vec![];
+                        n.indices(&mut cell_indices);
+                        let (above, text, below) = split_figures(&cell_indices, spans, figures);
+                        if text.len() > 0 || above.len() > 0 || below.len() > 0 {
+                            indices.extend(text);
+                            let bbox = indices[lines.last().map_or(0, |l| l.1)..].iter()
+                                .flat_map(|&i| spans.get(i))
+                                .map(|s| s.rect)
+                                .reduce(|a, b| a.union_rect(b))
+                                .unwrap_or_default();
+                            lines.push((bbox, indices.len(), above, below));
                         }
                     }
+                    if indices.len() == 0 {
+                        for (_, _, above, below) in lines {
+                            flow.runs.extend(above.into_iter().chain(below).map(|f| figure::figure_run(f, spans)));
+                        }
+                        return;
+                    }
 
                     let para_spans = indices.iter().flat_map(|&i| spans.get(i));
                     let class = classify(para_spans.clone());
                     // the bounding box the paragraph
-                    let bbox = lines.iter().map(|t| t.0).reduce(|a, b| a.union_rect(b)).unwrap();
+                    let bbox = lines.iter().filter(|l| l.0 != RectF::default()).map(|l| l.0).reduce(|a, b| a.union_rect(b)).unwrap();
                     let line_height = avg(para_spans.map(|s| s.rect.height())).unwrap();
                     
                     // classify the lines by this vertical line
@@ -169,7 +450,7 @@ pub(crate) fn build<E: Encoder>(mut flow: &mut Flow, spans: &[TextSpan<E>], node
                     let mut left = 0;
                     let mut right = 0;
 
-                    for (line_bbox, _) in lines.iter() {
+                    for (line_bbox, ..) in lines.iter().filter(|l| l.0 != RectF::default()) {
                         if line_bbox.min_x() >= left_margin {
                             right += 1;
                         } else {
@@ -198,81 +479,125 @@ pub(crate) fn build<E: Encoder>(mut flow: &mut Flow, spans: &[TextSpan<E>], node
                     // the second line will be treated as as another run, but actually it should be in 
                     // in the same run.
 
-                    let mut para_start = 0;
-                    let mut line_start = 0;
+                    // Display formulas and figures get runs of their own, consecutive formula lines form one formula.
                     let mut text = String::new();
-                    let mut para_bbox = RectF::default();
-                    let mut flow_lines = vec![];
-                    for &(line_bbox, end) in lines.iter() {
-                        if line_start != 0 {
-                            //Always add a line break for new line, which will be treated as whitespace in the concat_text method
-                            text.push('\n');
-
-                            // if a line is indented(indent = true) or outdented(indent = false), it marks a new paragraph
-                            // so here, save previous lines as a new run.
-                            if (line_bbox.min_x() >= left_margin) == indent {
-                                flow.runs.push(Run {
-                                    lines: take(&mut flow_lines),
-                                    kind: match class {
-                                        Class::Header => RunType::Header,
-                                        _ => RunType::Paragraph
-                                    },
-                                });
-                                para_start = line_start;
-                            }
+                    let mut para_lines = vec![];
+                    let mut formula_spans = vec![];
+                    let mut line_start = 0;
+                    for (line_bbox, end, above, below) in lines {
+                        let line_spans: Vec<&TextSpan<E>> = indices[line_start..end].iter().flat_map(|&i| spans.get(i)).collect();
+                        line_start = end;
+
+                        let is_formula = line_spans.len() > 0 && math::is_formula(&line_spans, rules);
+                        if above.len() > 0 || is_formula {
+                            flow.runs.extend(paragraph_runs(take(&mut para_lines), class, left_margin, indent, line_height));
+                        }
+                        if formula_spans.len() > 0 && !is_formula {
+                            flow.runs.push(math::formula_run(&take(&mut formula_spans), rules));
                         }
-                        if end > line_start {
-                            let words = concat_text(&mut text, indices[line_start..end].iter().flat_map(|&i| spans.get(i)));
+                        flow.runs.extend(above.into_iter().map(|f| figure::figure_run(f, spans)));
 
-                            if words.len() > 0 {
-                                flow_lines.push(Line { words , rect: line_bbox.into()});
+                        if is_formula {
+                            formula_spans.extend(line_spans);
+                        } else if line_spans.len() > 0 {
+                            if text.len() != 0 {
+                                //Always add a line break for new line, which will be treated as whitespace in the concat_text method
+                                text.push('\n');
                             }
+                            let words = concat_text(&mut text, line_spans.iter().cloned());
+                            para_lines.push((line_bbox, words));
                         }
-                        if para_start == line_start {
-                            para_bbox = line_bbox;
-                        } else {
-                            para_bbox = para_bbox.union_rect(line_bbox);
-                        }
-                        line_start = end;
-                    }
 
-                    flow.runs.push(Run {
-                        lines: flow_lines,
-                        kind: match class {
-                            Class::Header => RunType::Header,
-                            _ => RunType::Paragraph
+                        if below.len() > 0 {
+                            flow.runs.extend(paragraph_runs(take(&mut para_lines), class, left_margin, indent, line_height));
+                            if formula_spans.len() > 0 {
+                                flow.runs.push(math::formula_run(&take(&mut formula_spans), rules));
+                            }
+                            flow.runs.extend(below.into_iter().map(|f| figure::figure_run(f, spans)));
                         }
-                    });
+                    }
+                    if formula_spans.len() > 0 {
+                        flow.runs.push(math::formula_run(&formula_spans, rules));
+                    }
+                    flow.runs.extend(paragraph_runs(para_lines, class, left_margin, indent, line_height));
                 }
                 NodeTag::Complex => {
                     let x_anchors = once(x_anchor).chain(x.iter().cloned()).cycle();
                     for (node, x) in cells.iter().zip(x_anchors) {
-                        build(flow, spans, node, x);
+                        build(flow, spans, node, x, rules, figures);
                     }
                 }
             }
         }
-        Node::Table { ref table } => {
-            if let Some(bbox) = table.values()
-                .flat_map(|v| v.value.iter().flat_map(|&i| spans.get(i).map(|s| s.rect)))
-                .reduce(|a, b| a.union_rect(b)) {
-                let table = table.flat_map(|indices| {
-                    if indices.len() == 0 {
-                        None
-                    } else {
-                        let line_spans = indices.iter().flat_map(|&i| spans.get(i));
-                        let bbox: RectF = line_spans.clone().map(|s| s.rect).reduce(|a, b| a.union_rect(b)).unwrap().into();
-
-                        let mut text = String::new();
-                        concat_text(&mut text, line_spans.clone());
-                        Some(CellContent {
-                            text,
-                            rect: bbox.into(),
-                        })
-                    }
-                });
-                flow.add_table(table);
+        Node::Table { ref table, header_rows, ref header } => {
+            if let Some(content) = table_content(spans, figures, table, header_rows, header) {
+                let index = flow.tables.len();
+                let mut cells: Vec<&CellContent> = content.table.values().map(|v| &v.value).collect();
+                cells.sort_by_key(|c| (c.row, c.col));
+                flow.runs.extend(cells.into_iter().filter(|c| c.lines.len() > 0).map(|c| Run {
+                    lines: c.lines.clone(),
+                    kind: RunType::Cell { table: index, row: c.row, col: c.col },
+                }));
+                flow.add_table(content);
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn table_content<E: Encoder>(spans: &[TextSpan<E>], figures: &[Figure], table: &Table<TableCell>, header_rows: usize, header: &[HeaderCell<TableCell>]) -> Option<TableContent> {
+    let mut content = |cell: &TableCell| cell_content(spans, figures, cell);
+    let cells = table.flat_map(&mut content);
+    let bbox = cells.values().map(|v| RectF::from(v.value.rect)).reduce(|a, b| a.union_rect(b))?;
+    Some(TableContent {
+        table: cells,
+        rect: bbox.into(),
+        rows: table.values().map(|v| v.value.row + v.value.rowspan).max().unwrap_or(0),
+        columns: table.values().map(|v| v.value.col + v.value.colspan).max().unwrap_or(0),
+        header_rows,
+        header: header.iter().filter_map(|c| c.flat_map(&mut content)).collect(),
+    })
+}
+
+/// The text of a table cell in lines and the figures in it, `None` if it is empty.
+fn cell_content<E: Encoder>(spans: &[TextSpan<E>], figures: &[Figure], cell: &TableCell) -> Option<CellContent> {
+    let (above, indices, below) = split_figures(&cell.indices, spans, figures);
+    let cell_figures: Vec<Rect> = above.into_iter().chain(below).map(|f| f.rect.into()).collect();
+    let cell_spans: Vec<&TextSpan<E>> = indices.iter().flat_map(|&i| spans.get(i)).collect();
+    let bbox = cell_spans.iter().map(|s| s.rect)
+        .chain(cell_figures.iter().map(|&r| r.into()))
+        .reduce(|a, b| a.union_rect(b))?;
+
+    let mut text = String::new();
+    let lines: Vec<Line> = math::rows(&cell_spans).into_iter()
+        .map(|row| {
+            if text.len() > 0 {
+                text.push(' ');
+            }
+            let rect = row.iter().map(|s| s.rect).reduce(|a, b| a.union_rect(b)).unwrap();
+            let words = concat_text(&mut text, row.iter().cloned());
+            Line { words, rect: rect.into() }
+        })
+        .collect();
+
+    let on_baseline = lines.iter()
+        .flat_map(|l| l.words.iter())
+        .all(|w| w.chars.iter().all(|c| c.position == VerticalPosition::Baseline));
+    Some(CellContent {
+        value: on_baseline.then(|| value::parse(&text)).flatten(),
+        text,
+        lines,
+        rect: bbox.into(),
+        grid_rect: cell.rect.into(),
+        font_size: avg(cell_spans.iter().map(|s| s.font_size)).unwrap_or(0.0),
+        bold: cell_spans.len() > 0 && cell_spans.iter().all(|s| s.font.as_ref().map_or(false, |f| f.name.contains("Bold"))),
+        figures: cell_figures,
+        row: cell.row,
+        col: cell.col,
+        rowspan: cell.rowspan,
+        colspan: cell.colspan,
+        nested: match cell.nested.as_deref() {
+            Some(&Node::Table { ref table, header_rows, ref header }) => table_content(spans, figures, table, header_rows, header).map(Box::new),
+            _ => None,
+        },
+    })
+}
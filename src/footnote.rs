@@ -0,0 +1,349 @@
+use pathfinder_geometry::rect::RectF;
+
+use crate::flow::{Flow, FootnoteRef, Line, Run, RunType, VerticalPosition, Word};
+use crate::util::median;
+
+const SYMBOLS: &[char] = &['*', '†', '‡', '§', '¶', '‖', '#'];
+
+fn superscript_digit(c: char) -> Option<char> {
+    Some(match c {
+        '⁰' => '0',
+        '¹' => '1',
+        '²' => '2',
+        '³' => '3',
+        '⁴' => '4',
+        '⁵' => '5',
+        '⁶' => '6',
+        '⁷' => '7',
+        '⁸' => '8',
+        '⁹' => '9',
+        _ => return None
+    })
+}
+
+/// Normalize a footnote label: "12", "¹²", "*", "††" or a single letter.
+pub fn parse_label(s: &str) -> Option<String> {
+    let digits: String = s.chars()
+        .map(|c| if c.is_ascii_digit() { Some(c) } else { superscript_digit(c) })
+        .collect::<Option<_>>()
+        .unwrap_or_default();
+    if digits.len() > 0 && digits.len() <= 3 {
+        return Some(digits);
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_lowercase() => return Some(s.into()),
+        (Some(c), _) if SYMBOLS.contains(&c) && s.chars().all(|d| d == c) && s.len() <= 3 * c.len_utf8() => return Some(s.into()),
+        _ => {}
+    }
+    None
+}
+
+/// The label at the start of a footnote body, either as a word of its own or glued to the first word ("1See …").
+fn leading_label(words: &[Word]) -> Option<String> {
+    let first = words.first()?;
+    if words.len() > 1 {
+        if let Some(label) = parse_label(&first.text) {
+            return Some(label);
+        }
+    }
+    let end = first.text.find(|c: char| !(c.is_ascii_digit() || superscript_digit(c).is_some() || SYMBOLS.contains(&c)))?;
+    if end > 0 {
+        parse_label(&first.text[..end])
+    } else {
+        None
+    }
+}
+
+/// Find footnote bodies at the bottom of the page and turn them into `RunType::Footnote` runs.
+///
+/// Footnotes are set in a smaller font than the body text. They are either below a short
+/// horizontal rule in the lower half of the page, or start with a label in the bottom quarter of the page.
+/// The rule starts at the left edge of the body text and has all of it above and only small text below,
+/// unlike the axis of a chart or the rules of a table.
+pub fn detect(flow: &mut Flow, bbox: RectF, rules: &[[f32; 4]]) {
+    let body_height = match median(flow.runs.iter().flat_map(|r| r.lines.iter()).map(|l| l.rect.h).collect()) {
+        Some(h) => h,
+        None => return,
+    };
+    let is_small = |run: &Run| run.lines.iter().all(|l| l.rect.h < 0.95 * body_height);
+    let body: Vec<RectF> = flow.runs.iter().filter(|r| !is_small(r)).filter_map(Run::bbox).collect();
+    let below = |y: f32| flow.runs.iter().filter(move |r| r.bbox().map_or(false, |b| b.min_y() >= y));
+    // only footnote text below, no captions
+    let notes_only = |run: &Run| is_small(run)
+        && !matches!(run.kind, RunType::Figure { .. } | RunType::Cell { .. })
+        && run.lines.first().map_or(true, |l| crate::caption::parse_label(&l.words).is_none());
+
+    // the separator: the lowest short horizontal rule in the lower half of the page that has text below it
+    let separator = rules.iter()
+        .filter(|&&[x1, y1, x2, y2]| {
+            let len = (x2 - x1).abs();
+            (y1 - y2).abs() < 1.0
+                && len > 0.05 * bbox.width() && len < 0.5 * bbox.width()
+                && y1 > bbox.min_y() + 0.5 * bbox.height()
+                && body.iter().any(|b| (b.min_x() - x1.min(x2)).abs() < body_height)
+        })
+        .map(|&[_, y, _, _]| y)
+        .filter(|&y| body.iter().all(|b| b.max_y() <= y + 1.0))
+        .filter(|&y| below(y).next().is_some() && below(y).all(notes_only))
+        .fold(None, |max: Option<f32>, y| Some(max.map_or(y, |m| m.max(y))));
+
+    let bottom_quarter = bbox.min_y() + 0.75 * bbox.height();
+
+    let runs = std::mem::take(&mut flow.runs);
+    for run in runs {
+        let run_bbox = match run.bbox() {
+            Some(r) => r,
+            None => {
+                flow.runs.push(run);
+                continue;
+            }
+        };
+        let small = is_small(&run);
+        let labeled = run.lines.first().and_then(|l| leading_label(&l.words)).is_some();
+        // a lone word below the rule is the page number
+        let is_footnote = small && !matches!(run.kind, RunType::Cell { .. }) && match separator {
+            Some(y) => run_bbox.min_y() >= y && (labeled || run.lines.iter().map(|l| l.words.len()).sum::<usize>() > 1),
+            None => labeled && run_bbox.min_y() >= bottom_quarter,
+        };
+        if is_footnote {
+            flow.runs.extend(split_footnotes(run.lines));
+        } else {
+            flow.runs.push(run);
+        }
+    }
+}
+
+/// One run per footnote: a line that starts with a label starts a new footnote.
+fn split_footnotes(lines: Vec<Line>) -> Vec<Run> {
+    let mut runs: Vec<Run> = vec![];
+    for line in lines {
+        let label = leading_label(&line.words);
+        match runs.last_mut() {
+            Some(run) if label.is_none() => run.lines.push(line),
+            _ => runs.push(Run { lines: vec![line], kind: RunType::Footnote { label } }),
+        }
+    }
+    runs
+}
+
+/// The superscript chars at the end of the word, like the "3" in "text³".
+fn superscript_suffix(word: &Word) -> Option<&str> {
+    if word.chars.is_empty() {
+        return None;
+    }
+    let start = word.chars.iter()
+        .rposition(|c| c.position != VerticalPosition::Superscript)
+        .map_or(0, |i| word.chars.get(i + 1).map_or(word.text.len(), |c| c.offset));
+    Some(&word.text[start..])
+}
+
+/// Find footnote markers in the body text and link them to the footnote with the same label.
+///
+/// Markers are superscript labels, either a word of their own or at the end of a word ("text³").
+/// Raised numbers without a matching footnote are left alone, they are more likely exponents.
+/// With `without_footnotes` the footnote runs are removed from the flow afterwards.
+pub fn link(flow: &mut Flow, without_footnotes: bool) {
+    let footnote = |label: &str| flow.runs.iter().position(|run| {
+        matches!(run.kind, RunType::Footnote { label: Some(ref l) } if l == label)
+    });
+
+    let mut refs = vec![];
+    for (run_idx, run) in flow.runs.iter().enumerate() {
+        if matches!(run.kind, RunType::Footnote { .. }) {
+            continue;
+        }
+        for (line_idx, line) in run.lines.iter().enumerate() {
+            for (word_idx, word) in line.words.iter().enumerate() {
+                if let Some(label) = superscript_suffix(word).and_then(parse_label) {
+                    if let Some(idx) = footnote(&label) {
+                        refs.push(FootnoteRef {
+                            label,
+                            run: run_idx,
+                            line: line_idx,
+                            word: word_idx,
+                            footnote: Some(idx),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    if without_footnotes {
+        // new index of every remaining run
+        let mut new_idx = vec![];
+        let mut count = 0;
+        for run in flow.runs.iter() {
+            new_idx.push(count);
+            if !matches!(run.kind, RunType::Footnote { .. }) {
+                count += 1;
+            }
+        }
+        flow.runs.retain(|run| !matches!(run.kind, RunType::Footnote { .. }));
+        for r in refs.iter_mut() {
+            r.run = new_idx[r.run];
+            r.footnote = None;
+        }
+        for run in flow.runs.iter_mut() {
+            if let RunType::Marginalia { anchor: Some(ref mut a) } | RunType::Sidebar { anchor: Some(ref mut a) } = run.kind {
+                *a = new_idx[*a];
+            }
+        }
+    }
+    flow.footnote_refs = refs;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{Char, Rect};
+    use crate::testing::words;
+
+    fn run(text: &str, x: f32, y: f32, h: f32) -> Run {
+        Run { lines: vec![Line { words: words(text, x, y, h), rect: Rect { x, y, w: 300.0, h } }], kind: RunType::Paragraph }
+    }
+
+    #[test]
+    fn test_detect() {
+        use pathfinder_geometry::vector::Vector2F;
+        let page = RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0));
+        let page_with = |runs: Vec<Run>| {
+            let mut flow = Flow::new();
+            flow.runs = runs;
+            flow
+        };
+        let is_footnote = |run: &Run| matches!(run.kind, RunType::Footnote { .. });
+
+        // a separator at the left edge of the body, below all of it
+        let mut flow = page_with(vec![
+            run("Body text here", 50.0, 500.0, 10.0),
+            run("more body text", 50.0, 600.0, 10.0),
+            run("1 See the source.", 50.0, 720.0, 8.0),
+        ]);
+        detect(&mut flow, page, &[[50.0, 700.0, 200.0, 700.0]]);
+        assert!(is_footnote(&flow.runs[2]));
+
+        // the axis of a chart between body paragraphs, with small labels below it
+        let mut flow = page_with(vec![
+            run("Body text here", 50.0, 450.0, 10.0),
+            run("axis labels", 120.0, 505.0, 8.0),
+            run("more body text", 50.0, 600.0, 10.0),
+        ]);
+        detect(&mut flow, page, &[[100.0, 500.0, 250.0, 500.0]]);
+        assert!(!flow.runs.iter().any(is_footnote));
+
+        // a caption below a rule at the bottom of the page
+        let mut flow = page_with(vec![
+            run("Body text here", 50.0, 500.0, 10.0),
+            run("Figure 2: Sales by region", 50.0, 720.0, 8.0),
+        ]);
+        detect(&mut flow, page, &[[50.0, 700.0, 200.0, 700.0]]);
+        assert!(!flow.runs.iter().any(is_footnote));
+
+        // a table in small print at the bottom of the page
+        let body = || (0..5).map(|l| run("Body text here", 50.0, 400.0 + 20.0 * l as f32, 10.0));
+        let cell = |text: &str, row, col, x| Run { kind: RunType::Cell { table: 0, row, col }, ..run(text, x, 700.0 + 15.0 * row as f32, 8.0) };
+        let mut flow = page_with(body().chain([
+            cell("1 kg", 0, 0, 50.0),
+            cell("Red apples", 0, 1, 100.0),
+            cell("2 kg", 1, 0, 50.0),
+            cell("Green pears", 1, 1, 100.0),
+        ]).collect());
+        detect(&mut flow, page, &[[50.0, 680.0, 200.0, 680.0]]);
+        assert!(!flow.runs.iter().any(is_footnote));
+        let mut flow = page_with(body().chain([cell("1 kg", 0, 0, 50.0), cell("Red apples", 0, 1, 100.0)]).collect());
+        detect(&mut flow, page, &[]);
+        assert!(!flow.runs.iter().any(is_footnote));
+    }
+
+    /// Mark the chars of `word` from byte `start` on as superscript, the others as on the baseline.
+    fn raise(word: &mut Word, start: usize) {
+        word.chars = word.text.char_indices().map(|(offset, _)| Char {
+            offset,
+            pos: word.rect.x,
+            width: 5.0,
+            position: if offset >= start { VerticalPosition::Superscript } else { VerticalPosition::Baseline },
+        }).collect();
+    }
+
+    /// A body line ending in a raised marker, "Some text¹" if `glued`.
+    fn marked(text: &str, label: &str, glued: bool, y: f32) -> Run {
+        let mut run = run(text, 50.0, y, 10.0);
+        let line = &mut run.lines[0];
+        if glued {
+            let word = line.words.last_mut().unwrap();
+            let start = word.text.len();
+            word.text.push_str(label);
+            raise(word, start);
+        } else {
+            let end = line.words.last().unwrap().rect;
+            let mut marker = words(label, end.x + end.w, y, 6.0);
+            raise(&mut marker[0], 0);
+            line.words.append(&mut marker);
+        }
+        run
+    }
+
+    fn note(label: &str, y: f32) -> Run {
+        Run {
+            kind: RunType::Footnote { label: Some(label.into()) },
+            ..run(&format!("{} A note.", label), 50.0, y, 8.0)
+        }
+    }
+
+    #[test]
+    fn test_link() {
+        let mut flow = Flow::new();
+        flow.runs = vec![
+            marked("Some text", "1", false, 100.0),
+            marked("More text", "²", true, 200.0),
+            // an exponent without a footnote
+            marked("An area in m", "3", false, 300.0),
+            // a number on the baseline, and one without chars
+            run("See chapter 1", 50.0, 400.0, 10.0),
+            note("1", 700.0),
+            note("2", 720.0),
+        ];
+        link(&mut flow, false);
+        let refs: Vec<_> = flow.footnote_refs.iter().map(|r| (r.label.as_str(), r.run, r.line, r.word, r.footnote)).collect();
+        assert_eq!(refs, [("1", 0, 0, 2, Some(4)), ("2", 1, 0, 1, Some(5))]);
+        assert_eq!(flow.runs.len(), 6);
+    }
+
+    #[test]
+    fn test_link_without_footnotes() {
+        let mut flow = Flow::new();
+        flow.runs = vec![
+            marked("Some text", "1", false, 100.0),
+            note("1", 700.0),
+            run("Text between the notes", 50.0, 710.0, 10.0),
+            note("2", 720.0),
+            marked("More text", "2", false, 730.0),
+            Run { kind: RunType::Marginalia { anchor: Some(4) }, ..run("Aside", 10.0, 730.0, 10.0) },
+        ];
+        link(&mut flow, true);
+        assert!(flow.runs.iter().all(|r| !matches!(r.kind, RunType::Footnote { .. })));
+        assert_eq!(flow.runs.len(), 4);
+        // the margin note still points at its run
+        assert_eq!(flow.runs[3].kind, RunType::Marginalia { anchor: Some(2) });
+        // the runs after the footnotes moved up
+        let refs: Vec<_> = flow.footnote_refs.iter().map(|r| (r.label.as_str(), r.run, r.footnote)).collect();
+        assert_eq!(refs, [("1", 0, None), ("2", 2, None)]);
+        assert_eq!(flow.runs[2].lines[0].words[0].text, "More");
+    }
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(parse_label("3"), Some("3".into()));
+        assert_eq!(parse_label("¹²"), Some("12".into()));
+        assert_eq!(parse_label("*"), Some("*".into()));
+        assert_eq!(parse_label("††"), Some("††".into()));
+        assert_eq!(parse_label("b"), Some("b".into()));
+
+        assert_eq!(parse_label("2024"), None);
+        assert_eq!(parse_label("B"), None);
+        assert_eq!(parse_label("*†"), None);
+        assert_eq!(parse_label(""), None);
+    }
+}
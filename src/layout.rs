@@ -0,0 +1,194 @@
+use font::Encoder;
+use pathfinder_geometry::rect::RectF;
+use pdf_render::TextSpan;
+use serde::{Serialize, Deserialize};
+
+use crate::figure::Figure;
+use crate::flow::Rect;
+use crate::node::{Node, NodeTag, TableCell};
+
+/// The kind of a layout node.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayoutTag {
+    /// A leaf: spans that were not split any further.
+    Singleton,
+    /// Cells next to each other, forming a line of text.
+    Line,
+    /// Lines on top of each other, forming a paragraph.
+    Paragraph,
+    /// Anything else, e.g. a page with columns.
+    Complex,
+    /// A table, the children are its cells in row-major order.
+    Table,
+}
+
+/// A span as seen by the layout analysis.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LayoutSpan {
+    pub text: String,
+    pub rect: Rect,
+    pub font_size: f32,
+}
+
+/// The place of a table cell in the grid of its table.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LayoutCell {
+    pub row: usize,
+    pub col: usize,
+    pub rowspan: usize,
+    pub colspan: usize,
+}
+
+/// A node of the layout tree.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LayoutNode {
+    pub tag: LayoutTag,
+    /// The bounding box of the spans in this node, `None` if it has none.
+    pub rect: Option<Rect>,
+    /// The x positions of the vertical gaps between the columns of children.
+    pub x_gaps: Vec<f32>,
+    /// The y positions of the horizontal gaps between the rows of children.
+    pub y_gaps: Vec<f32>,
+    /// Indices of all spans in this node, in reading order.
+    /// An index `i` refers to `Layout::spans[i]`, or to the figure `Layout::figures[i - spans.len()]` past the spans.
+    pub spans: Vec<usize>,
+    pub children: Vec<LayoutNode>,
+    /// The place in the grid, for the children of a table.
+    pub cell: Option<LayoutCell>,
+}
+
+/// The result of the layout analysis of a page, the tree that `Flow` is built from.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Layout {
+    /// The page box.
+    pub bbox: Rect,
+    /// The spans that took part in the analysis. The page number is not among them.
+    pub spans: Vec<LayoutSpan>,
+    /// The bounds of the figures: images and drawings
+    pub figures: Vec<Rect>,
+    pub root: LayoutNode,
+}
+
+impl Layout {
+    pub(crate) fn new<E: Encoder>(spans: &[TextSpan<E>], figures: &[Figure], root: &Node, bbox: RectF) -> Layout {
+        Layout {
+            bbox: bbox.into(),
+            spans: spans.iter().map(|s| LayoutSpan {
+                text: s.text.clone(),
+                rect: s.rect.into(),
+                font_size: s.font_size,
+            }).collect(),
+            figures: figures.iter().map(|f| f.rect.into()).collect(),
+            root: LayoutNode::new(spans, figures, root),
+        }
+    }
+}
+
+impl LayoutNode {
+    fn new<E: Encoder>(spans: &[TextSpan<E>], figures: &[Figure], node: &Node) -> LayoutNode {
+        let mut indices = vec![];
+        node.indices(&mut indices);
+        let rect = indices.iter()
+            .filter_map(|&i| spans.get(i).map(|s| s.rect).or_else(|| figures.get(i - spans.len()).map(|f| f.rect)))
+            .reduce(|a, b| a.union_rect(b))
+            .map(Rect::from);
+
+        let (tag, x_gaps, y_gaps, children) = match *node {
+            Node::Final { .. } => (LayoutTag::Singleton, vec![], vec![], vec![]),
+            Node::Grid { ref x, ref y, ref cells, tag } => {
+                let tag = match tag {
+                    NodeTag::Singleton => LayoutTag::Singleton,
+                    NodeTag::Line => LayoutTag::Line,
+                    NodeTag::Paragraph => LayoutTag::Paragraph,
+                    NodeTag::Complex => LayoutTag::Complex,
+                };
+                (tag, x.clone(), y.clone(), cells.iter().map(|n| LayoutNode::new(spans, figures, n)).collect())
+            }
+            Node::Table { ref table, .. } => {
+                let mut cells: Vec<&TableCell> = table.values().map(|v| &v.value).collect();
+                cells.sort_by_key(|c| (c.row, c.col));
+                let children = cells.into_iter()
+                    .map(|cell| LayoutNode {
+                        cell: Some(LayoutCell { row: cell.row, col: cell.col, rowspan: cell.rowspan, colspan: cell.colspan }),
+                        ..match cell.nested {
+                            Some(ref nested) => LayoutNode::new(spans, figures, nested),
+                            None => LayoutNode::new(spans, figures, &Node::Final { indices: cell.indices.clone() }),
+                        }
+                    })
+                    .collect();
+                (LayoutTag::Table, vec![], vec![], children)
+            }
+        };
+        LayoutNode { tag, rect, x_gaps, y_gaps, spans: indices, children, cell: None }
+    }
+
+    /// Visit this node and all nodes below it, parents before their children.
+    pub fn walk(&self, f: &mut impl FnMut(&LayoutNode, usize)) {
+        self._walk(f, 0)
+    }
+    fn _walk(&self, f: &mut impl FnMut(&LayoutNode, usize), depth: usize) {
+        f(self, depth);
+        for child in self.children.iter() {
+            child._walk(f, depth + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::span;
+    use pathfinder_geometry::vector::Vector2F;
+
+    fn page() -> RectF {
+        RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0))
+    }
+
+    #[test]
+    fn test_grid() {
+        let spans = [span("First line", 50.0, 110.0, 10.0), span("Second line", 50.0, 125.0, 10.0)];
+        let root = Node::Grid {
+            x: vec![],
+            y: vec![112.5],
+            cells: vec![Node::Final { indices: vec![0] }, Node::Final { indices: vec![1] }],
+            tag: NodeTag::Paragraph,
+        };
+        let layout = Layout::new(&spans, &[], &root, page());
+
+        assert_eq!(layout.spans.len(), 2);
+        assert_eq!(layout.spans[1].text, "Second line");
+        let root = &layout.root;
+        assert_eq!(root.tag, LayoutTag::Paragraph);
+        assert_eq!(root.y_gaps, [112.5]);
+        assert_eq!(root.spans, [0, 1]);
+        assert_eq!(root.rect, Some(spans[0].rect.union_rect(spans[1].rect).into()));
+        assert!(root.children.iter().all(|c| c.tag == LayoutTag::Singleton && c.cell.is_none()));
+
+        let mut depths = vec![];
+        root.walk(&mut |node, depth| depths.push((node.spans.clone(), depth)));
+        assert_eq!(depths, [(vec![0, 1], 0), (vec![0], 1), (vec![1], 1)]);
+    }
+
+    #[test]
+    fn test_table() {
+        // a header spanning both columns, and two cells below it
+        let spans = [span("Size", 50.0, 110.0, 10.0), span("12", 50.0, 125.0, 10.0), span("15", 100.0, 125.0, 10.0)];
+        let cell = |row, col, colspan, indices| TableCell { row, col, rowspan: 1, colspan, rect: RectF::default(), indices, nested: None };
+        let mut table = table::Table::empty(2, 2);
+        for cell in [cell(1, 1, 1, vec![2]), cell(0, 0, 2, vec![0]), cell(1, 0, 1, vec![1])] {
+            let (row, col, colspan) = (cell.row as u32, cell.col as u32, cell.colspan as u32);
+            table.set_cell(cell, row, col, 1, colspan);
+        }
+        let layout = Layout::new(&spans, &[], &Node::Table { table, header_rows: 0, header: vec![] }, page());
+
+        let root = &layout.root;
+        assert_eq!(root.tag, LayoutTag::Table);
+        let cells: Vec<_> = root.children.iter().map(|c| (c.cell.unwrap(), c.spans.clone())).collect();
+        assert_eq!(cells, [
+            (LayoutCell { row: 0, col: 0, rowspan: 1, colspan: 2 }, vec![0]),
+            (LayoutCell { row: 1, col: 0, rowspan: 1, colspan: 1 }, vec![1]),
+            (LayoutCell { row: 1, col: 1, rowspan: 1, colspan: 1 }, vec![2]),
+        ]);
+        assert_eq!(root.children[2].rect, Some(spans[2].rect.into()));
+    }
+}
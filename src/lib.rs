@@ -1,30 +1,101 @@
 use std::collections::HashSet;
+use std::sync::Arc;
 
-use flow::Flow;
-use pathfinder_geometry::transform2d::Transform2F;
-use pdf::{backend::Backend, object::{Page, Resolve}, PdfError};
-use pdf_render::{tracer::{TraceCache, Tracer, DrawItem}, Fill, render_pattern, render_page, FillMode, font::OutlineBuilder};
+use analyzer::{LayoutAnalyzer, XyCut};
+use figure::Figure;
+use flow::{Flow, Rect};
+use layout::Layout;
+use node::Node;
+use pathfinder_geometry::{rect::RectF, transform2d::Transform2F, vector::Vector2F};
+use pathfinder_content::outline::Outline;
+use pdf::{backend::Backend, object::{Page, Resolve, XObject}, PdfError};
+use pdf_render::{tracer::{TraceCache, Tracer, DrawItem}, Fill, render_pattern, render_page, FillMode, font::OutlineBuilder, TextSpan};
 
 mod node;
 mod util;
 mod text;
 mod classify;
+mod list;
+mod caption;
+mod footnote;
+mod math;
+mod margin;
+mod figure;
+mod dropcap;
+mod debug;
+#[cfg(test)]
+mod testing;
+pub mod analyzer;
 pub mod flow;
+pub mod layout;
+pub mod value;
+pub mod page_number;
+pub mod stitch;
+pub mod csv;
 
+#[derive(Clone, Debug, Default)]
+pub struct ExtractOptions {
+    /// Leave out running headers, footers and page numbers.
+    pub without_header_and_footer: bool,
+    /// Leave the footnote bodies out of the flow. Markers in the text are still reported in `Flow::footnote_refs`.
+    pub without_footnotes: bool,
+    /// Index of the page in the document (starting at 0), used to look up its label in the catalog's `/PageLabels`.
+    pub page_nr: Option<u32>,
+    /// The algorithm that splits the page into blocks, [`XyCut`] if not set.
+    pub analyzer: Option<Arc<dyn LayoutAnalyzer>>,
+    /// Only extract what is inside these rectangles (in page space), like `pdftotext -x -y -W -H`.
+    /// The whole page if empty.
+    pub regions: Vec<Rect>,
+    /// Leave out what is inside these rectangles (in page space), like stamps or logos.
+    pub masks: Vec<Rect>,
+}
+
+/// Extract the text flow of a page, see [run_with_options] for the other options.
 pub fn run<B: Backend>(file: &pdf::file::CachedFile<B>, page: &Page, resolve: &impl Resolve, transform: Transform2F, without_header_and_footer: bool) -> Result<Flow, PdfError> {
-    let mut cache = TraceCache::new(OutlineBuilder::default());
+    let options = ExtractOptions { without_header_and_footer, ..Default::default() };
+    run_with_options(file, page, resolve, transform, &options)
+}
+
+/// Extract the text of a page as a flow of runs, with the given options.
+///
+/// All coordinates in the result are in page space: points, y pointing down, with the origin in the top left corner
+/// of the page as displayed, that is turned by its `/Rotate`, and then mapped by `transform`.
+/// If most of the text runs sideways or upside down, the page is turned so that it reads upright
+/// and the coordinates are those of the turned page, see `Flow::rotation`.
+pub fn run_with_options<B: Backend>(file: &pdf::file::CachedFile<B>, page: &Page, resolve: &impl Resolve, transform: Transform2F, options: &ExtractOptions) -> Result<Flow, PdfError> {
+    analyze(file, page, resolve, transform, options).map(|a| a.flow)
+}
 
+/// Like [`run_with_options`], but also returns the layout tree the flow was built from.
+pub fn run_with_layout<B: Backend>(file: &pdf::file::CachedFile<B>, page: &Page, resolve: &impl Resolve, transform: Transform2F, options: &ExtractOptions) -> Result<(Flow, Layout), PdfError> {
+    let analysis = analyze(file, page, resolve, transform, options)?;
+    let layout = Layout::new(&analysis.spans, &analysis.figures, &analysis.root, analysis.bbox);
+    Ok((analysis.flow, layout))
+}
+
+/// Render the layout analysis of the page as an SVG document, for debugging.
+///
+/// Shows the text spans, vector drawings, ruling lines, the layout tree and the runs of the flow on top of each other.
+pub fn debug_svg<B: Backend>(file: &pdf::file::CachedFile<B>, page: &Page, resolve: &impl Resolve, transform: Transform2F, options: &ExtractOptions) -> Result<String, PdfError> {
+    let a = analyze(file, page, resolve, transform, options)?;
+    Ok(debug::svg(&a.spans, &a.root, &a.flow, a.bbox, &a.lines, &a.outlines))
+}
+
+/// Trace the page and the patterns used on it.
+///
+/// Returns the view box, the draw items, those of the patterns first, and the transform from user space to the page.
+fn trace(cache: &mut TraceCache<OutlineBuilder>, page: &Page, resolve: &impl Resolve, transform: Transform2F) -> Result<(RectF, Vec<DrawItem<OutlineBuilder>>, Transform2F), PdfError> {
     let mut clip_paths = vec![];
-    let mut tracer = Tracer::new(&mut cache, &mut clip_paths);
+    let mut tracer = Tracer::new(cache, &mut clip_paths);
 
     //Get text, pattern, image by the Tracer backend.
-    render_page(&mut tracer, resolve, page, transform)?;
+    let root = render_page(&mut tracer, resolve, page, transform)?;
 
     let bbox = tracer.view_box();
-    let items: Vec<DrawItem<OutlineBuilder>> = tracer.finish();
+    let page_items: Vec<DrawItem<OutlineBuilder>> = tracer.finish();
     //Get all patterns which may have lines and texts inside.
     let mut patterns = HashSet::new();
-    for item in items.iter() {
+    for item in page_items.iter() {
         if let DrawItem::Vector(ref v) = item {
             if let Some(FillMode { color: Fill::Pattern(id), .. }) = v.fill {
                 patterns.insert(id);
@@ -35,36 +106,8 @@ pub fn run<B: Backend>(file: &pdf::file::CachedFile<B>, page: &Page, resolve: &i
         }
     }
 
-    let mut spans = vec![];
-    let mut lines = vec![];
-
-    let mut visit_item = |item| {
-        match item {
-            DrawItem::Text(t, _) if bbox.intersects(t.rect) => {
-                spans.push(t);
-            }
-            DrawItem::Vector(path) if bbox.intersects(path.outline.bounds()) => {
-                for contour in path.outline.contours() {
-                    use pathfinder_content::{outline::ContourIterFlags, segment::SegmentKind};
-                    for segment in contour.iter(ContourIterFlags::empty()) {
-                        match segment.kind {
-                            SegmentKind::Line => lines.push([
-                                segment.baseline.from_x(),
-                                segment.baseline.from_y(),
-                                segment.baseline.to_x(),
-                                segment.baseline.to_y()
-                            ]),
-                            _ => {}
-                        }
-                    }
-                }
-
-            }
-            _ => {}
-        }
-    };
-
     // Analyze patterns to get lines and texts.
+    let mut items = vec![];
     for &p in patterns.iter() {
         let pattern = match resolve.get(p) {
             Ok(p) => p,
@@ -73,25 +116,399 @@ pub fn run<B: Backend>(file: &pdf::file::CachedFile<B>, page: &Page, resolve: &i
                 continue;
             }
         };
-        let mut pat_tracer = Tracer::new(&mut cache, &mut clip_paths);
+        let mut pat_tracer = Tracer::new(cache, &mut clip_paths);
 
         render_pattern(&mut pat_tracer, &*pattern, resolve)?;
-        let pat_items = pat_tracer.finish();
-        for item in pat_items {
-            visit_item(item);
+        items.extend(pat_tracer.finish());
+    }
+    items.extend(page_items);
+    Ok((bbox, items, root))
+}
+
+/// The part of the page's `/Rotate` that `root`, the transform the page was rendered with, does not turn it by yet,
+/// in degrees clockwise: 0, 90, 180 or 270.
+///
+/// `root` maps the user space of the page, y pointing up, to the page space, after `transform`.
+fn missing_rotation(rotate: i32, transform: Transform2F, root: Transform2F) -> u32 {
+    let flip = Transform2F::from_scale(Vector2F::new(1.0, -1.0));
+    let turned = transform.matrix.inverse() * root.matrix * flip.matrix;
+    let dir = turned * Vector2F::new(1.0, 0.0);
+    let applied = ((dir.y().atan2(dir.x()).to_degrees() / 90.0).round() as i32) * 90;
+    (rotate - applied).rem_euclid(360) as u32
+}
+
+/// The parts of the line segment inside any of the `regions`, or all of it if there are none, and outside of the `masks`.
+fn clip_segment([x1, y1, x2, y2]: [f32; 4], regions: &[RectF], masks: &[RectF]) -> Vec<[f32; 4]> {
+    let (a, d) = (Vector2F::new(x1, y1), Vector2F::new(x2 - x1, y2 - y1));
+    // the range of t for which a + t d is inside the rect
+    let inside = |r: &RectF| {
+        let (mut t0, mut t1) = (0.0f32, 1.0f32);
+        for (p, q) in [(-d.x(), a.x() - r.min_x()), (d.x(), r.max_x() - a.x()), (-d.y(), a.y() - r.min_y()), (d.y(), r.max_y() - a.y())] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        (t0 <= t1).then_some((t0, t1))
+    };
+
+    let mut parts: Vec<(f32, f32)> = match regions.is_empty() {
+        true => vec![(0.0, 1.0)],
+        false => regions.iter().filter_map(inside).collect(),
+    };
+    for mask in masks {
+        if let Some((m0, m1)) = inside(mask) {
+            parts = parts.into_iter()
+                .flat_map(|(t0, t1)| [(t0, t1.min(m0)), (t0.max(m1), t1)])
+                .filter(|&(t0, t1)| t0 < t1)
+                .collect();
+        }
+    }
+    parts.into_iter()
+        .map(|(t0, t1)| {
+            let (p, q) = (a + d * t0, a + d * t1);
+            [p.x(), p.y(), q.x(), q.y()]
+        })
+        .collect()
+}
+
+/// Turn the page clockwise by `degrees`, keeping the top left corner of `bbox` in place.
+fn turn(bbox: RectF, degrees: f32) -> Transform2F {
+    let rotate = Transform2F::from_rotation(degrees.to_radians());
+    let rotated = rotate * bbox;
+    Transform2F::from_translation(bbox.origin() - rotated.origin()) * rotate
+}
+
+/// The direction most of the text runs in, in degrees clockwise: 0, 90, 180 or 270.
+///
+/// Counts the chars of the spans by the direction of their baseline.
+/// Returns 0 unless more than half of the chars run in another direction.
+fn text_rotation(items: &[DrawItem<OutlineBuilder>]) -> u32 {
+    let mut counts = [0usize; 4];
+    for item in items {
+        if let DrawItem::Text(ref span, _) = item {
+            let dir = span.transform.matrix * Vector2F::new(1.0, 0.0);
+            let angle = dir.y().atan2(dir.x()).to_degrees();
+            let quadrant = ((angle / 90.0).round() as i32).rem_euclid(4) as usize;
+            counts[quadrant] += span.chars.len();
+        }
+    }
+    let total: usize = counts.iter().sum();
+    match (1..4).find(|&q| 2 * counts[q] > total) {
+        Some(q) => 90 * q as u32,
+        None => 0,
+    }
+}
+
+/// The straight segments of a vector path as `[x1, y1, x2, y2]`, whether it is made of horizontal and vertical
+/// segments only, and whether it is a filled box, like the background of a table row, maybe with rounded corners.
+///
+/// `filled` is whether the path is filled and not stroked. Thin boxes are rules, not boxes.
+fn path_shape(outline: &Outline, filled: bool) -> (Vec<[f32; 4]>, bool, bool) {
+    use pathfinder_content::{outline::ContourIterFlags, segment::SegmentKind};
+    let bounds = outline.bounds();
+    let mut segments = vec![];
+    let mut ruling = true;
+    // the length of the horizontal and vertical sides
+    let mut straight = 0.0;
+    for contour in outline.contours() {
+        for segment in contour.iter(ContourIterFlags::empty()) {
+            match segment.kind {
+                SegmentKind::Line => {
+                    let v = segment.baseline.vector();
+                    if v.x().abs() < 0.5 || v.y().abs() < 0.5 {
+                        straight += v.length();
+                    } else {
+                        ruling = false;
+                    }
+                    segments.push([
+                        segment.baseline.from_x(),
+                        segment.baseline.from_y(),
+                        segment.baseline.to_x(),
+                        segment.baseline.to_y()
+                    ]);
+                }
+                SegmentKind::None => {}
+                _ => ruling = false,
+            }
         }
     }
+    let filled_box = filled && outline.contours().len() == 1 && segments.len() <= 5
+        && bounds.width().min(bounds.height()) >= 2.0
+        && straight >= 1.6 * (bounds.width() + bounds.height());
+    (segments, ruling, filled_box)
+}
+
+/// The edges of the filled boxes behind text, like zebra stripes or shaded cells of a table without rules,
+/// that are not among the `lines` yet, like those of a box with rounded corners.
+///
+/// They bound the rows and cells of the table the same way rules do.
+/// Boxes covering half of the page or more are page backgrounds.
+fn fill_edges<'a>(fills: &'a [(RectF, Fill)], spans: &'a [TextSpan<OutlineBuilder>], lines: &'a [[f32; 4]], bbox: RectF) -> impl Iterator<Item=[f32; 4]> + 'a {
+    let page_area = bbox.width() * bbox.height();
+    let same = |a: [f32; 4], b: [f32; 4]| a.iter().zip(b).all(|(p, q)| (p - q).abs() < 0.5);
+    fills.iter()
+        .map(|&(f, _)| f)
+        .filter(move |f| f.width() * f.height() < 0.5 * page_area)
+        .filter(move |f| spans.iter().any(|s| f.contains_point(s.rect.center())))
+        .flat_map(|f| {
+            let (a, b) = (f.origin(), f.lower_right());
+            [
+                [a.x(), a.y(), b.x(), a.y()],
+                [a.x(), b.y(), b.x(), b.y()],
+                [a.x(), a.y(), a.x(), b.y()],
+                [b.x(), a.y(), b.x(), b.y()],
+            ]
+        })
+        .filter(move |&edge| !lines.iter().any(|&[x1, y1, x2, y2]| same(edge, [x1, y1, x2, y2]) || same(edge, [x2, y2, x1, y1])))
+}
+
+/// Everything the extraction of a page produced along the way.
+struct Analysis {
+    flow: Flow,
+    spans: Vec<TextSpan<OutlineBuilder>>,
+    figures: Vec<Figure>,
+    root: Node,
+    bbox: RectF,
+    lines: Vec<[f32; 4]>,
+    outlines: Vec<Outline>,
+}
+
+fn analyze<B: Backend>(file: &pdf::file::CachedFile<B>, page: &Page, resolve: &impl Resolve, transform: Transform2F, options: &ExtractOptions) -> Result<Analysis, PdfError> {
+    let mut cache = TraceCache::new(OutlineBuilder::default());
+    let (mut bbox, mut items, root) = trace(&mut cache, page, resolve, transform)?;
+
+    // Show the page the way it is displayed, turned by its `/Rotate`, if rendering did not do that already.
+    let mut transform = transform;
+    let missing = missing_rotation(page.rotate, transform, root);
+    if missing != 0 {
+        transform = turn(bbox, missing as f32) * transform;
+        (bbox, items, _) = trace(&mut cache, page, resolve, transform)?;
+    }
+
+    // Turn the page so that most of the text reads upright, e.g. a landscape table printed on a portrait page.
+    let rotation = text_rotation(&items);
+    if rotation != 0 {
+        (bbox, items, _) = trace(&mut cache, page, resolve, turn(bbox, -(rotation as f32)) * transform)?;
+    }
+
+    let mut content = Content::default();
+    let mut visit_item = |item| {
+        match item {
+            DrawItem::Text(t, _) if bbox.intersects(t.rect) => {
+                content.spans.push(t);
+            }
+            DrawItem::Vector(path) if bbox.intersects(path.outline.bounds()) => {
+                let fill = match path.stroke {
+                    None => path.fill.map(|f| f.color),
+                    Some(_) => None,
+                };
+                content.push_path(path.outline, fill);
+            }
+            DrawItem::Image(image) if bbox.intersects(image.rect) => {
+                content.drawings.push(image.rect);
+                let size = match resolve.get(image.id) {
+                    Ok(xobject) => match *xobject {
+                        XObject::Image(ref img) => Some((img.width, img.height)),
+                        _ => None,
+                    },
+                    Err(e) => {
+                        log::warn!("failed to load image: {:?}", e);
+                        None
+                    }
+                };
+                content.images.push((image.rect, size));
+            }
+            _ => {}
+        }
+    };
 
     // After this loop, all the text and lines are ready for further processing.
     for item in items {
         visit_item(item);
     }
 
-    let root = node::build(&spans, bbox, &lines, without_header_and_footer);
+    let page_label = options.page_nr.and_then(|nr| {
+        file.get_root().page_labels.as_ref().and_then(|labels| page_number::page_label(labels, resolve, nr))
+    });
+    let mut analysis = analyze_content(content, bbox, page_label, Some(file.num_pages()), options);
+    analysis.flow.rotation = rotation;
+    Ok(analysis)
+}
+
+/// What the page is made of, sorted out of the draw items.
+#[derive(Default)]
+struct Content {
+    spans: Vec<TextSpan<OutlineBuilder>>,
+    lines: Vec<[f32; 4]>,
+    /// bounds of images and vector drawings, which captions may refer to
+    drawings: Vec<RectF>,
+    /// the vector graphics themselves, for debug output
+    outlines: Vec<Outline>,
+    /// image placements with their size in pixels
+    images: Vec<(RectF, Option<(u32, u32)>)>,
+    /// the bounds of vector paths with whether they are just rules
+    paths: Vec<(RectF, bool)>,
+    /// filled boxes with their colour, like shaded table rows or cells
+    fills: Vec<(RectF, Fill)>,
+}
+
+impl Content {
+    /// Sort a vector path into the drawings, its segments into the lines and, if it is a filled box, into the fills.
+    /// `fill` is the colour of a path that is filled and not stroked.
+    fn push_path(&mut self, outline: Outline, fill: Option<Fill>) {
+        let bounds = outline.bounds();
+        self.drawings.push(bounds);
+        let (segments, ruling, filled_box) = path_shape(&outline, fill.is_some());
+        if let Some(fill) = fill.filter(|_| filled_box) {
+            self.fills.push((bounds, fill));
+        }
+        self.lines.extend(segments);
+        self.paths.push((bounds, ruling));
+        self.outlines.push(outline);
+    }
+}
+
+/// The analysis of the content of a page, from the spans and drawings to the flow.
+fn analyze_content(content: Content, mut bbox: RectF, page_label: Option<String>, page_count: Option<u32>, options: &ExtractOptions) -> Analysis {
+    let Content { mut spans, mut lines, mut drawings, outlines, mut images, mut paths, mut fills } = content;
+
+    // Page numbers are found at the edge of the page, not of the regions.
+    let page_bbox = bbox;
+
+    // Restrict the page to the regions of interest, before any analysis.
+    // Spans and drawings are kept if their center is inside, line segments are cut at the edges.
+    if options.regions.len() > 0 || options.masks.len() > 0 {
+        let regions: Vec<RectF> = options.regions.iter().map(|&r| r.into()).collect();
+        let masks: Vec<RectF> = options.masks.iter().map(|&r| r.into()).collect();
+        let wanted = |r: RectF| {
+            let c = r.center();
+            (regions.is_empty() || regions.iter().any(|a| a.contains_point(c)))
+                && !masks.iter().any(|m| m.contains_point(c))
+        };
+        spans.retain(|s| wanted(s.rect));
+        drawings.retain(|&r| wanted(r));
+        images.retain(|&(r, _)| wanted(r));
+        paths.retain(|&(r, _)| wanted(r));
+        fills.retain(|&(r, _)| wanted(r));
+        lines = lines.into_iter().flat_map(|line| clip_segment(line, &regions, &masks)).collect();
+
+        if let Some(area) = regions.iter().cloned().reduce(|a, b| a.union_rect(b)) {
+            bbox = area.intersection(bbox).unwrap_or(area);
+        }
+    }
+
+    // The printed page number is page metadata. Like the rest of the header and footer,
+    // it is only taken out of the text when those are not wanted.
+    let page_number = page_number::detect(&spans, page_bbox, page_label.as_deref(), page_count).map(|(number, indices)| {
+        if options.without_header_and_footer {
+            util::remove_indices(&mut spans, &indices);
+        }
+        number
+    });
+
+    dropcap::merge(&mut spans);
+    let figures = figure::detect(&images, &paths, &spans, bbox);
+
+    let analyzer = options.analyzer.as_deref().unwrap_or(&XyCut);
+    // the table detection takes shaded rows and cells for rules
+    let layout_rules: Vec<[f32; 4]> = lines.iter().cloned().chain(fill_edges(&fills, &spans, &lines, bbox)).collect();
+    let (root, margins) = node::build(&spans, bbox, &layout_rules, &fills, options.without_header_and_footer, analyzer, &figures);
 
     let mut flow = Flow::new();
+    flow.page_number = page_number;
+    flow.page_label = page_label;
   
-    flow::build(&mut flow, &spans, &root, bbox.min_x());
+    flow::build(&mut flow, &spans, &root, bbox.min_x(), &lines, &figures);
+    footnote::detect(&mut flow, bbox, &lines);
+    list::drop_lone_items(&mut flow.runs);
+    list::assign_levels(&mut flow.runs);
+    caption::link(&mut flow, &caption::drawing_regions(&drawings, bbox));
+    margin::detect(&mut flow, &margins, bbox);
+    footnote::link(&mut flow, options.without_footnotes);
+
+    Analysis { flow, spans, figures, root, bbox, lines, outlines }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_content::outline::Contour;
+    use crate::testing::{rounded_rect, span};
+
+    #[test]
+    fn test_clip_segment() {
+        let rect = |x0, y0, x1, y1| RectF::from_points(Vector2F::new(x0, y0), Vector2F::new(x1, y1));
+        let region = [rect(100.0, 100.0, 300.0, 300.0)];
 
-    Ok(flow)
-}
\ No newline at end of file
+        // a rule across the region is cut at its edges, not dropped because its center is outside
+        assert_eq!(clip_segment([0.0, 150.0, 1000.0, 150.0], &region, &[]), [[100.0, 150.0, 300.0, 150.0]]);
+        assert_eq!(clip_segment([150.0, 0.0, 150.0, 400.0], &region, &[]), [[150.0, 100.0, 150.0, 300.0]]);
+        assert!(clip_segment([0.0, 50.0, 1000.0, 50.0], &region, &[]).is_empty());
+        // a mask cuts a piece out
+        let mask = [rect(180.0, 140.0, 220.0, 160.0)];
+        assert_eq!(clip_segment([0.0, 150.0, 1000.0, 150.0], &region, &mask), [[100.0, 150.0, 180.0, 150.0], [220.0, 150.0, 300.0, 150.0]]);
+        assert_eq!(clip_segment([0.0, 150.0, 1000.0, 150.0], &[], &mask), [[0.0, 150.0, 180.0, 150.0], [220.0, 150.0, 1000.0, 150.0]]);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let flip = Transform2F::row_major(1.0, 0.0, 0.0, -1.0, 0.0, 842.0);
+        let transform = Transform2F::from_scale(2.0);
+
+        // rendered without its /Rotate 90
+        assert_eq!(missing_rotation(90, transform, transform * flip), 90);
+        assert_eq!(missing_rotation(-90, transform, transform * flip), 270);
+        // rendered with it
+        let rotated = transform * Transform2F::from_rotation(90f32.to_radians()) * flip;
+        assert_eq!(missing_rotation(90, transform, rotated), 0);
+        assert_eq!(missing_rotation(0, transform, rotated), 270);
+
+        // a portrait page turned by 90° lies in landscape at the same origin, its top left corner coming from the bottom left
+        let page = RectF::new(Vector2F::new(0.0, 0.0), Vector2F::new(595.0, 842.0));
+        let turned = turn(page, 90.0);
+        let landscape = turned * page;
+        assert!((landscape.origin() - page.origin()).length() < 1e-3);
+        assert!((landscape.size() - Vector2F::new(842.0, 595.0)).length() < 1e-3);
+        assert!((turned * Vector2F::new(0.0, 842.0) - Vector2F::new(0.0, 0.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_fill_boxes() {
+        let page = RectF::new(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0));
+        let spans = [span("Alice", 150.0, 130.0, 10.0)];
+        let stripe = RectF::new(Vector2F::new(140.0, 110.0), Vector2F::new(340.0, 30.0));
+        let grey = || Fill::Solid(0.9, 0.9, 0.9);
+
+        // a filled rectangle is a box, its edges are among the lines already
+        let (segments, ruling, filled_box) = path_shape(&Outline::from_rect(stripe), true);
+        assert!(ruling && filled_box);
+        assert_eq!(fill_edges(&[(stripe, grey())], &spans, &segments, page).count(), 0);
+        // stroked it is just rules, and so is a thin one
+        assert!(!path_shape(&Outline::from_rect(stripe), false).2);
+        assert!(!path_shape(&Outline::from_rect(RectF::new(stripe.origin(), Vector2F::new(340.0, 1.0))), true).2);
+
+        // a box with rounded corners adds the edges its shorter sides leave out
+        let rounded = rounded_rect(stripe, 4.0);
+        let (segments, ruling, filled_box) = path_shape(&rounded, true);
+        assert!(!ruling && filled_box);
+        assert_eq!(fill_edges(&[(rounded.bounds(), grey())], &spans, &segments, page).count(), 4);
+        // a box without text on it is no table row
+        assert_eq!(fill_edges(&[(rounded.bounds(), grey())], &[], &segments, page).count(), 0);
+
+        // a filled triangle, like an arrow head, is no box
+        let mut contour = Contour::new();
+        for p in [stripe.origin(), stripe.upper_right(), stripe.lower_right()] {
+            contour.push_endpoint(p);
+        }
+        contour.close();
+        let mut triangle = Outline::new();
+        triangle.push_contour(contour);
+        assert!(!path_shape(&triangle, true).2);
+    }
+}
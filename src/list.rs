@@ -0,0 +1,265 @@
+use pathfinder_geometry::rect::RectF;
+
+use crate::flow::{Line, Run, RunType, Word};
+use crate::util::{is_number, parse_roman};
+
+/// Characters that mark an unordered list item.
+const BULLETS: &[char] = &[
+    '•', '◦', '▪', '▫', '■', '□', '●', '○', '◆', '◇', '‣', '⁃', '·', '►', '▶', '➢', '✓', '✔', '–', '—', '-', '*'
+];
+
+#[derive(Debug, PartialEq)]
+pub struct Marker {
+    pub text: String,
+    /// The position in the list for numbered items, `None` for bullets.
+    pub ordinal: Option<u32>,
+}
+impl From<Marker> for RunType {
+    fn from(marker: Marker) -> Self {
+        RunType::ListItem {
+            marker: marker.text,
+            ordinal: marker.ordinal,
+            level: 0,
+        }
+    }
+}
+
+/// Recognize a list item marker in the first word of a line.
+///
+/// Accepts bullets ("•", "–", "▪") and numbered markers like "1.", "2)", "1.2.", "a)", "(iv)".
+/// The marker has to be the whole word, so "-5", "•Item" or "*important*" are no markers.
+pub fn parse_marker(word: &str) -> Option<Marker> {
+    let mut chars = word.chars();
+    let first = chars.next()?;
+    if BULLETS.contains(&first) {
+        if chars.next().is_some() {
+            return None;
+        }
+        return Some(Marker { text: first.into(), ordinal: None });
+    }
+
+    let inner = if let Some(rest) = word.strip_prefix('(') {
+        rest.strip_suffix(')')?
+    } else {
+        word.strip_suffix(')').or_else(|| word.strip_suffix('.'))?
+    };
+    let ordinal = parse_ordinal(inner)?;
+
+    Some(Marker { text: word.into(), ordinal: Some(ordinal) })
+}
+
+fn parse_ordinal(s: &str) -> Option<u32> {
+    // "1.2" counts as the second item of a nested list
+    let last = s.rsplit('.').next()?;
+    if s.split('.').all(|part| is_number(part) && part.len() <= 3) {
+        return last.parse().ok();
+    }
+
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        // a single "i" is far more likely to start a roman list than to be the 9th letter
+        (Some(c), None) if c.is_ascii_alphabetic() && !matches!(c, 'i' | 'I') => {
+            Some(c.to_ascii_lowercase() as u32 - 'a' as u32 + 1)
+        }
+        _ => parse_roman(s).filter(|&n| n <= 50),
+    }
+}
+
+/// The marker of a line, if it starts a list item: a marker followed by a space and text.
+pub fn line_marker(words: &[Word]) -> Option<Marker> {
+    match words {
+        [first, _, ..] => parse_marker(&first.text),
+        _ => None,
+    }
+}
+
+/// Whether the lines of a paragraph form a list rather than text that happens to have a line
+/// starting with a dash or "(a)", or with initials like "A. Smith".
+///
+/// It takes an item whose next line hangs indented behind its marker, or two items in a row.
+pub fn is_list(lines: &[(RectF, Vec<Word>)], line_height: f32) -> bool {
+    let markers: Vec<usize> = (0 .. lines.len()).filter(|&i| line_marker(&lines[i].1).is_some()).collect();
+    // the line continues the item above it, starting right of its marker
+    let behind = |marker: usize, line: usize| lines[line].0.min_x() > lines[marker].0.min_x() + 0.5 * line_height;
+
+    let hanging = markers.iter().any(|&i| match lines.get(i + 1) {
+        Some((next, words)) if line_marker(words).is_none() => {
+            let text_x = lines[i].1[1].rect.x;
+            behind(i, i + 1) && (next.min_x() - text_x).abs() < 0.5 * line_height
+        }
+        _ => false,
+    });
+    let in_a_row = markers.windows(2).any(|w| (w[0] + 1 .. w[1]).all(|l| behind(w[0], l)));
+    hanging || in_a_row
+}
+
+/// Split the lines of a paragraph into list items.
+///
+/// A line with a marker starts a new item. Lines without a marker continue the current item
+/// unless they are outdented left of its marker. The other lines form runs of `kind`.
+pub fn split_items(lines: Vec<(RectF, Vec<Word>)>, kind: RunType, line_height: f32) -> Vec<Run> {
+    let mut runs = vec![];
+    let mut current = Run { lines: vec![], kind: kind.clone() };
+    let mut marker_x = None;
+
+    for (bbox, words) in lines {
+        if words.is_empty() {
+            continue;
+        }
+        let marker = line_marker(&words);
+        let continues = match (&marker, marker_x) {
+            (None, Some(x)) => bbox.min_x() > x - 0.5 * line_height,
+            (None, None) => true,
+            (Some(_), _) => false,
+        };
+        if !continues {
+            if current.lines.len() > 0 {
+                runs.push(current);
+            }
+            marker_x = marker.as_ref().map(|_| bbox.min_x());
+            current = Run {
+                lines: vec![],
+                kind: marker.map(RunType::from).unwrap_or_else(|| kind.clone()),
+            };
+        }
+        current.lines.push(Line { words, rect: bbox.into() });
+    }
+    if current.lines.len() > 0 {
+        runs.push(current);
+    }
+    runs
+}
+
+/// Turn list items of a single line back into paragraphs, unless another item is next to them.
+///
+/// A line that ends up in a run of its own, like "A. Smith wrote" or "- and rising.", only reads as
+/// a list item next to others. Items of several lines passed [is_list] already.
+pub fn drop_lone_items(runs: &mut [Run]) {
+    let is_item = |r: &Run| matches!(r.kind, RunType::ListItem { .. });
+    let lone: Vec<usize> = (0 .. runs.len())
+        .filter(|&i| is_item(&runs[i]) && runs[i].lines.len() == 1)
+        .filter(|&i| !(i > 0 && is_item(&runs[i - 1])) && !runs.get(i + 1).map_or(false, is_item))
+        .collect();
+    for i in lone {
+        runs[i].kind = RunType::Paragraph;
+    }
+}
+
+/// Assign the nesting level of consecutive list items from the indentation of their markers.
+pub fn assign_levels(runs: &mut [Run]) {
+    let mut start = 0;
+    while start < runs.len() {
+        let len = runs[start..].iter()
+            .take_while(|r| matches!(r.kind, RunType::ListItem { .. }))
+            .count();
+        if len == 0 {
+            start += 1;
+            continue;
+        }
+        let items = &mut runs[start .. start + len];
+        start += len;
+
+        let em = items.iter()
+            .filter_map(|r| r.lines.first())
+            .map(|l| l.rect.h)
+            .fold(0.0, f32::max);
+
+        // the distinct x positions of the markers, each is one level deeper than the previous
+        let mut stops: Vec<f32> = vec![];
+        for x in items.iter().map(marker_x) {
+            if !stops.iter().any(|&s| (s - x).abs() < 0.5 * em) {
+                stops.push(x);
+            }
+        }
+
+        for run in items.iter_mut() {
+            let x = marker_x(run);
+            if let RunType::ListItem { ref mut level, .. } = run.kind {
+                *level = stops.iter().filter(|&&s| s + 0.5 * em < x).count();
+            }
+        }
+    }
+}
+
+fn marker_x(run: &Run) -> f32 {
+    run.lines.first()
+        .and_then(|l| l.words.first())
+        .map_or(0.0, |w| w.rect.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_marker() {
+        let bullet = |s: &str| Some(Marker { text: s.into(), ordinal: None });
+        let numbered = |s: &str, n| Some(Marker { text: s.into(), ordinal: Some(n) });
+
+        assert_eq!(parse_marker("•"), bullet("•"));
+        assert_eq!(parse_marker("–"), bullet("–"));
+        assert_eq!(parse_marker("▪Item"), None);
+        assert_eq!(parse_marker("1."), numbered("1.", 1));
+        assert_eq!(parse_marker("12)"), numbered("12)", 12));
+        assert_eq!(parse_marker("2.3."), numbered("2.3.", 3));
+        assert_eq!(parse_marker("a)"), numbered("a)", 1));
+        assert_eq!(parse_marker("(c)"), numbered("(c)", 3));
+        assert_eq!(parse_marker("(iv)"), numbered("(iv)", 4));
+        assert_eq!(parse_marker("i."), numbered("i.", 1));
+
+        assert_eq!(parse_marker("-5"), None);
+        assert_eq!(parse_marker("etc."), None);
+        assert_eq!(parse_marker("word"), None);
+        assert_eq!(parse_marker("2024."), None);
+        assert_eq!(parse_marker("(did)"), None);
+    }
+
+    fn line(x: f32, text: &str) -> (RectF, Vec<Word>) {
+        use pathfinder_geometry::vector::Vector2F;
+
+        let words = crate::testing::words(text, x, 0.0, 10.0);
+        let end = words.last().unwrap().rect;
+        (RectF::from_points(Vector2F::new(x, 0.0), Vector2F::new(end.x + end.w, 10.0)), words)
+    }
+
+    #[test]
+    fn test_is_list() {
+        // a wrapped line of a paragraph that starts with a dash
+        let text = vec![line(0.0, "The range is wide"), line(0.0, "- and rising.")];
+        assert!(!is_list(&text, 10.0));
+        // initials
+        let names = vec![line(0.0, "A. Smith wrote"), line(0.0, "about it.")];
+        assert!(!is_list(&names, 10.0));
+
+        // an item with a hanging indent
+        let item = vec![line(0.0, "a) The first item"), line(15.0, "goes on")];
+        assert!(is_list(&item, 10.0));
+        // two items in a row
+        let items = vec![line(0.0, "• One"), line(0.0, "• Two")];
+        assert!(is_list(&items, 10.0));
+
+        let runs = split_items(vec![line(0.0, "Steps"), line(0.0, "• One"), line(0.0, "• Two")], RunType::Header, 10.0);
+        assert_eq!(runs[0].kind, RunType::Header);
+        assert!(matches!(runs[1].kind, RunType::ListItem { .. }));
+    }
+
+    #[test]
+    fn test_drop_lone_items() {
+        let run = |text: &str| {
+            let (bbox, words) = line(0.0, text);
+            let kind = line_marker(&words).map(RunType::from).unwrap_or(RunType::Paragraph);
+            Run { lines: vec![Line { words, rect: bbox.into() }], kind }
+        };
+
+        // lines on their own that start like a list item
+        let mut runs = vec![run("The range is wide"), run("- and rising."), run("Later on"), run("A. Smith wrote about it.")];
+        assert!(matches!(runs[1].kind, RunType::ListItem { .. }));
+        drop_lone_items(&mut runs);
+        assert!(runs.iter().all(|r| r.kind == RunType::Paragraph));
+
+        // items next to each other stay
+        let mut runs = vec![run("Steps"), run("1. Open it"), run("2. Close it")];
+        drop_lone_items(&mut runs);
+        assert!(runs[1..].iter().all(|r| matches!(r.kind, RunType::ListItem { .. })));
+    }
+}
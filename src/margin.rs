@@ -0,0 +1,96 @@
+use pathfinder_geometry::{rect::RectF, vector::Vector2F};
+
+use crate::flow::{Flow, RunType};
+
+/// Turn the runs in the margin regions into `RunType::Marginalia` or `RunType::Sidebar` runs,
+/// anchored to the body run closest to them.
+///
+/// The runs of a region are grouped into blocks separated by more than two lines of space.
+/// A block taller than a fifth of the page is a sidebar, the runs of a smaller block are margin notes.
+pub fn detect(flow: &mut Flow, regions: &[RectF], bbox: RectF) {
+    if regions.is_empty() {
+        return;
+    }
+    let regions: Vec<RectF> = regions.iter().map(|r| r.dilate(Vector2F::splat(1.0))).collect();
+    let in_margin = |r: &RectF| regions.iter().any(|m| m.contains_rect(*r));
+    let rects: Vec<Option<RectF>> = flow.runs.iter().map(|run| run.bbox()).collect();
+    let body: Vec<(usize, RectF)> = rects.iter().enumerate()
+        .filter_map(|(i, r)| r.filter(|r| !in_margin(r)).map(|r| (i, r)))
+        .filter(|&(i, _)| !matches!(flow.runs[i].kind, RunType::Footnote { .. }))
+        .collect();
+
+    for region in regions.iter() {
+        // figures, captions and table cells keep their type, only text becomes margin notes
+        let mut runs: Vec<(usize, RectF)> = rects.iter().enumerate()
+            .filter_map(|(i, r)| r.filter(|r| region.contains_rect(*r)).map(|r| (i, r)))
+            .filter(|&(i, _)| !matches!(flow.runs[i].kind, RunType::Figure { .. } | RunType::Caption { .. } | RunType::Cell { .. }))
+            .collect();
+        runs.sort_by(|a, b| a.1.min_y().partial_cmp(&b.1.min_y()).unwrap());
+
+        let mut blocks: Vec<(RectF, Vec<usize>)> = vec![];
+        for (i, r) in runs {
+            let line_height = flow.runs[i].lines.first().map_or(r.height(), |l| l.rect.h);
+            match blocks.last_mut() {
+                Some((block, members)) if r.min_y() - block.max_y() < 2.0 * line_height => {
+                    *block = block.union_rect(r);
+                    members.push(i);
+                }
+                _ => blocks.push((r, vec![i])),
+            }
+        }
+
+        for (block, members) in blocks {
+            let sidebar = block.height() > 0.2 * bbox.height();
+            for i in members {
+                let anchor = closest(&body, rects[i].unwrap());
+                flow.runs[i].kind = match sidebar {
+                    true => RunType::Sidebar { anchor },
+                    false => RunType::Marginalia { anchor },
+                };
+            }
+        }
+    }
+}
+
+/// The body run that overlaps the most vertically, or else the closest one.
+fn closest(body: &[(usize, RectF)], r: RectF) -> Option<usize> {
+    let overlap = |b: &RectF| b.max_y().min(r.max_y()) - b.min_y().max(r.min_y());
+    body.iter()
+        .max_by(|a, b| overlap(&a.1).partial_cmp(&overlap(&b.1)).unwrap())
+        .map(|&(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{Line, Rect, Run};
+    use crate::testing::words;
+    use pathfinder_geometry::vector::Vector2F;
+
+    fn run(text: &str, x: f32, y: f32, kind: RunType) -> Run {
+        let words = words(text, x, y, 10.0);
+        let end = words.last().unwrap().rect;
+        Run { lines: vec![Line { words, rect: Rect { x, y, w: end.x + end.w - x, h: 10.0 } }], kind }
+    }
+
+    #[test]
+    fn test_detect() {
+        let page = RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0));
+        let mut flow = Flow::new();
+        flow.runs = vec![
+            run("The body text of the page", 100.0, 100.0, RunType::Paragraph),
+            run("Note", 10.0, 100.0, RunType::Paragraph),
+            run("Fig. 1", 10.0, 300.0, RunType::Caption { target: None }),
+            run("Label", 10.0, 400.0, RunType::Figure { rect: Rect { x: 5.0, y: 380.0, w: 40.0, h: 40.0 }, image_size: None }),
+            run("2024", 10.0, 200.0, RunType::Cell { table: 0, row: 0, col: 0 }),
+        ];
+        let margin = RectF::from_points(Vector2F::new(5.0, 100.0), Vector2F::new(45.0, 420.0));
+        detect(&mut flow, &[margin], page);
+
+        assert_eq!(flow.runs[0].kind, RunType::Paragraph);
+        assert_eq!(flow.runs[1].kind, RunType::Marginalia { anchor: Some(0) });
+        assert_eq!(flow.runs[2].kind, RunType::Caption { target: None });
+        assert!(matches!(flow.runs[3].kind, RunType::Figure { .. }));
+        assert_eq!(flow.runs[4].kind, RunType::Cell { table: 0, row: 0, col: 0 });
+    }
+}
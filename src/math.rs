@@ -0,0 +1,225 @@
+use font::Encoder;
+use pathfinder_geometry::{rect::RectF, vector::Vector2F};
+use pdf_render::TextSpan;
+
+use crate::flow::{Line, Run, RunType};
+use crate::text::concat_text;
+
+/// Families of fonts used for math, in upper case without spaces and dashes: TeX (CMMI, CMSY, CMEX, MSAM, MSBM,
+/// Latin Modern Math), OpenType math fonts (Cambria Math, STIX Two Math, XITS Math) and MathType (MT Extra, Symbol).
+/// Only the start of the name is compared, so text fonts like "STIX Two Text" or "Euclid Circular" are no math fonts.
+const MATH_FONTS: &[&str] = &[
+    "CMMI", "CMSY", "CMEX", "CMBSY", "MSAM", "MSBM", "EUFM", "EUSM", "RSFS", "ESINT", "WASY",
+    "LMMATH", "LATINMODERNMATH", "NEWCMMATH", "CAMBRIAMATH", "STIXMATH", "STIXTWOMATH", "XITSMATH", "ASANAMATH",
+    "TEXGYRETERMESMATH", "TEXGYREPAGELLAMATH", "TEXGYREBONUMMATH", "TEXGYRESCHOLAMATH", "TEXGYREDEJAVUMATH",
+    "SYMBOL", "MTEXTRA", "MTSYMBOL", "EUCLIDSYMBOL", "EUCLIDEXTRA", "EUCLIDMATHONE", "EUCLIDMATHTWO", "MATHEMATICALPI",
+];
+
+pub fn is_math_font(name: &str) -> bool {
+    // the tag of a subset, like "ABCDEF+CMMI10"
+    let name = match name.split_once('+') {
+        Some((tag, rest)) if tag.len() == 6 && tag.chars().all(|c| c.is_ascii_uppercase()) => rest,
+        _ => name,
+    };
+    let name: String = name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    MATH_FONTS.iter().any(|f| name.starts_with(f))
+}
+
+/// Operators, arrows and the math alphanumerics.
+/// Plain greek letters are not included, that would turn greek text into formulas.
+pub fn is_math_char(c: char) -> bool {
+    matches!(c,
+        '=' | '+' | '<' | '>' | '^' | '|' | '±' | '×' | '÷' | '¬'
+        | '\u{2032}' ..= '\u{2037}' // primes
+        | '\u{2190}' ..= '\u{21ff}' // arrows
+        | '\u{2200}' ..= '\u{22ff}' // mathematical operators
+        | '\u{2308}' ..= '\u{230b}' // ceiling and floor
+        | '\u{27c0}' ..= '\u{27ef}' // misc mathematical symbols
+        | '\u{2980}' ..= '\u{2aff}' // more operators
+        | '\u{1d400}' ..= '\u{1d7ff}' // mathematical alphanumeric symbols
+    )
+}
+
+/// Whether the spans look like a (part of a) display formula.
+///
+/// That is the case if most chars are set in math fonts or are math symbols,
+/// or if a good part of them are and the spans are stacked on several baselines or have a fraction bar among them.
+/// A single char, like a bullet set in a symbol font, is no formula.
+pub fn is_formula<E: Encoder>(spans: &[&TextSpan<E>], rules: &[[f32; 4]]) -> bool {
+    let (math, total) = count_math(spans.iter().map(|s| (s.font.as_ref().map(|f| f.name.as_str()), s.text.as_str())));
+    if total < 2 {
+        return false;
+    }
+    let ratio = math as f32 / total as f32;
+    if ratio > 0.6 {
+        return true;
+    }
+    if ratio < 0.25 {
+        return false;
+    }
+
+    let bbox = match spans.iter().map(|s| s.rect).reduce(|a, b| a.union_rect(b)) {
+        Some(bbox) => bbox,
+        None => return false,
+    };
+    rows(spans).len() >= 3 || fraction_bars(bbox, rules).next().is_some()
+}
+
+/// The number of math chars, set in a math font or math symbols, and of all chars but whitespace,
+/// in text parts given with the name of their font.
+fn count_math<'a>(parts: impl Iterator<Item=(Option<&'a str>, &'a str)>) -> (usize, usize) {
+    let mut total = 0;
+    let mut math = 0;
+    for (font, text) in parts {
+        let math_font = font.map_or(false, is_math_font);
+        for c in text.chars().filter(|c| !c.is_whitespace()) {
+            total += 1;
+            if math_font || is_math_char(c) {
+                math += 1;
+            }
+        }
+    }
+    (math, total)
+}
+
+/// Horizontal rules inside the box, with room above and below.
+fn fraction_bars(bbox: RectF, rules: &[[f32; 4]]) -> impl Iterator<Item=(f32, f32, f32)> + '_ {
+    rules.iter()
+        .filter(|&&[_, y1, _, y2]| (y1 - y2).abs() < 1.0)
+        .map(|&[x1, y, x2, _]| (x1.min(x2), x1.max(x2), y))
+        .filter(move |&(x1, x2, y)| {
+            y > bbox.min_y() + 2.0 && y < bbox.max_y() - 2.0
+                && x1 >= bbox.min_x() - 2.0 && x2 <= bbox.max_x() + 2.0
+        })
+}
+
+/// Group the spans into rows of vertically overlapping spans, top to bottom.
+pub(crate) fn rows<'a, E: Encoder>(spans: &[&'a TextSpan<E>]) -> Vec<Vec<&'a TextSpan<E>>> {
+    let mut sorted = spans.to_vec();
+    sorted.sort_by(|a, b| a.rect.center().y().partial_cmp(&b.rect.center().y()).unwrap());
+
+    let mut rows: Vec<(RectF, Vec<&TextSpan<E>>)> = vec![];
+    for span in sorted {
+        match rows.last_mut() {
+            Some((rect, row)) if span.rect.center().y() < rect.max_y() => {
+                *rect = rect.union_rect(span.rect);
+                row.push(span);
+            }
+            _ => rows.push((span.rect, vec![span])),
+        }
+    }
+    rows.into_iter()
+        .map(|(_, mut row)| {
+            row.sort_by(|a, b| a.rect.min_x().partial_cmp(&b.rect.min_x()).unwrap());
+            row
+        })
+        .collect()
+}
+
+/// Build a `RunType::Formula` run, one line per row of the formula.
+///
+/// The text is linearized row by row, rows separated by a fraction bar become "(numerator)/(denominator)".
+pub fn formula_run<E: Encoder>(spans: &[&TextSpan<E>], rules: &[[f32; 4]]) -> Run {
+    let mut lines = vec![];
+    let mut parts: Vec<(RectF, String)> = vec![];
+    let mut out = String::new();
+    for row in rows(spans) {
+        let rect = row.iter().map(|s| s.rect).reduce(|a, b| a.union_rect(b)).unwrap();
+        let words = concat_text(&mut out, row.iter().cloned());
+        let text = words.iter().map(|w| w.marked_text()).collect::<Vec<_>>().join(" ");
+        parts.push((rect, text));
+        lines.push(Line { words, rect: rect.into() });
+        out.push('\n');
+    }
+
+    let mut text = String::new();
+    let mut parts = parts.into_iter().peekable();
+    while let Some((rect, part)) = parts.next() {
+        let fraction = parts.peek().filter(|(below, _)| {
+            let gap = RectF::from_points(
+                rect.lower_left().min(below.origin()),
+                rect.lower_right().max(below.upper_right())
+            );
+            fraction_bars(gap.dilate(Vector2F::new(0.0, 2.5)), rules).next().is_some()
+        }).is_some();
+
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        if fraction {
+            let (_, denominator) = parts.next().unwrap();
+            text.push_str(&format!("({})/({})", part, denominator));
+        } else {
+            text.push_str(&part);
+        }
+    }
+
+    let rect = spans.iter().map(|s| s.rect).reduce(|a, b| a.union_rect(b)).unwrap_or_default();
+    Run {
+        lines,
+        kind: RunType::Formula { rect: rect.into(), text },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::span;
+
+    #[test]
+    fn test_is_formula() {
+        // a sum with its limits on three rows
+        let sum = [span("n", 103.0, 90.0, 6.0), span("∑ x", 100.0, 102.0, 12.0), span("i=1", 101.0, 110.0, 6.0)];
+        assert!(is_formula(&sum.iter().collect::<Vec<_>>(), &[]));
+        assert!(!is_formula(&[&sum[1], &sum[2]], &[]));
+
+        // a prose line, set in an italic text font or not
+        let text = "where the sum runs over all values";
+        assert!(!is_formula(&[&span(text, 100.0, 100.0, 10.0)], &[]));
+        assert_eq!(count_math([(Some("Times-Italic"), text)].into_iter()), (0, 28));
+        assert_eq!(count_math([(Some("CMMI10"), "xy"), (Some("CMR10"), "+1")].into_iter()), (3, 4));
+        // a lone symbol, like a bullet
+        assert!(!is_formula(&[&span("∙", 100.0, 100.0, 10.0)], &[]));
+
+        // some math chars, stacked over a fraction bar
+        let numerator = span("a + b", 100.0, 100.0, 10.0);
+        let denominator = span("2", 110.0, 115.0, 10.0);
+        let bar = [100.0, 102.5, 125.0, 102.5];
+        assert!(is_formula(&[&numerator, &denominator], &[bar]));
+        assert!(!is_formula(&[&numerator, &denominator], &[]));
+    }
+
+    #[test]
+    fn test_formula_run() {
+        let text = |run: Run| match run.kind {
+            RunType::Formula { text, .. } => text,
+            kind => panic!("{:?}", kind),
+        };
+        let numerator = span("a + b", 100.0, 100.0, 10.0);
+        let denominator = span("2", 110.0, 115.0, 10.0);
+        let run = formula_run(&[&numerator, &denominator], &[[100.0, 102.5, 125.0, 102.5]]);
+        assert_eq!(run.lines.len(), 2);
+        assert_eq!(text(run), "(a + b)/(2)");
+        // without the bar it is two rows
+        assert_eq!(text(formula_run(&[&numerator, &denominator], &[])), "a + b 2");
+
+        let base = span("a + b", 100.0, 100.0, 10.0);
+        let exponent = span("2", 125.0, 96.0, 6.0);
+        let index = span("1", 125.0, 102.0, 6.0);
+        assert_eq!(text(formula_run(&[&base, &exponent], &[])), "a + b^2");
+        assert_eq!(text(formula_run(&[&base, &index], &[])), "a + b_1");
+    }
+
+    #[test]
+    fn test_is_math_font() {
+        for name in ["CMMI10", "ABCDEF+CMSY7", "CambriaMath", "Cambria Math", "STIXTwoMath-Regular", "XITSMath", "Symbol", "MT-Extra"] {
+            assert!(is_math_font(name), "{}", name);
+        }
+        for name in ["STIXTwoText-Italic", "EuclidCircularA-Regular", "Mathilde", "ABCDEF+Helvetica", "CMR10"] {
+            assert!(!is_math_font(name), "{}", name);
+        }
+    }
+}
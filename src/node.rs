@@ -1,50 +1,108 @@
+mod column;
 mod gap;
 mod line;
-mod render;
+pub mod render;
 mod table;
+mod whitespace;
 
 use gap::{dist_x, dist_y, gaps, left_right_gap, top_bottom_gap};
 use line::{analyze_lines, overlapping_lines, Lines};
-use pdf_render::TextSpan;
+pub use table::{HeaderCell, TableCell};
+use pdf_render::{font::OutlineBuilder, Fill, TextSpan};
 use pathfinder_geometry::rect::RectF;
 
 
+use crate::analyzer::LayoutAnalyzer;
 use crate::classify::{classify, Class};
-use crate::util::avg;
+use crate::figure::Figure;
+use crate::util::{avg, median};
 
 #[cfg(feature="ocr")]
 use tesseract_plumbing::Text;
 
+use std::collections::HashSet;
 use std::mem::take;
 use font::Encoder;
 
-pub fn build<E: Encoder>(spans: &[TextSpan<E>], bbox: RectF, lines: &[[f32; 4]], without_header_and_footer: bool) -> Node {
-    if spans.len() == 0 {
-        return Node::singleton(&[]);
+/// Build the layout tree of the page.
+///
+/// Narrow regions in the left and right margin are analyzed on their own and come after the body in the tree.
+/// Figures are boxes like the spans, the text on top of them is left out.
+/// Returns the tree and the bounding boxes of those margin regions.
+pub fn build(spans: &[TextSpan<OutlineBuilder>], bbox: RectF, lines: &[[f32; 4]], fills: &[(RectF, Fill)], without_header_and_footer: bool, analyzer: &dyn LayoutAnalyzer, figures: &[Figure]) -> (Node, Vec<RectF>) {
+    if spans.len() == 0 && figures.len() == 0 {
+        return (Node::singleton(&[]), vec![]);
     }
 
-    let mut boxes: Vec<(RectF, usize)> = spans.iter().enumerate().map(|(i, t)| (t.rect, i)).collect();
+    let on_figure: HashSet<usize> = figures.iter().flat_map(|f| f.spans.iter().cloned()).collect();
+    let mut boxes: Vec<(RectF, usize)> = spans.iter().enumerate()
+        .filter(|(i, _)| !on_figure.contains(i))
+        .map(|(i, t)| (t.rect, i))
+        .chain(figures.iter().enumerate().map(|(k, f)| (f.rect, spans.len() + k)))
+        .collect();
     let mut boxes = boxes.as_mut_slice();
     if without_header_and_footer {
         boxes = exclude_header_and_footer(boxes, bbox, spans);
     }
 
-    let lines = analyze_lines(lines);
-    
-    split(&mut boxes, &spans, &lines)
+    let (left, body, right) = split_margins(boxes, bbox);
+    let body = analyzer.analyze(spans, body, lines, fills, bbox);
+
+    let mut regions = vec![];
+    let mut cells = vec![];
+    for side in [left, right] {
+        if side.len() == 0 {
+            continue;
+        }
+        // running heads set sideways in the margin
+        if without_header_and_footer && side.iter().all(|&(_, i)| spans.get(i).map_or(false, is_rotated)) {
+            continue;
+        }
+        regions.push(side.iter().map(|&(r, _)| r).reduce(|a, b| a.union_rect(b)).unwrap());
+        cells.push(analyzer.analyze(spans, side, lines, fills, bbox));
+    }
+    if cells.len() == 0 {
+        return (body, regions);
+    }
+    cells.insert(0, body);
+    let root = Node::Grid {
+        x: vec![],
+        y: vec![],
+        cells,
+        tag: NodeTag::Complex,
+    };
+    (root, regions)
+}
+
+/// Find columns, then split each of them with the XY cut.
+pub(crate) fn xy_cut<E: Encoder>(spans: &[TextSpan<E>], boxes: &mut [(RectF, usize)], lines: &[[f32; 4]], fills: &[(RectF, Fill)]) -> Node {
+    if boxes.len() == 0 {
+        return Node::singleton(&[]);
+    }
+    let lines = Lines { fills: fills.to_vec(), ..analyze_lines(lines) };
+
+    column::split_columns(boxes, spans, &lines)
+        .unwrap_or_else(|| split(boxes, spans, &lines))
+}
+
+/// Split the page into blocks at the separators of the whitespace cover.
+pub(crate) fn whitespace_cover<E: Encoder>(spans: &[TextSpan<E>], boxes: &mut [(RectF, usize)], lines: &[[f32; 4]], fills: &[(RectF, Fill)]) -> Node {
+    let lines = Lines { fills: fills.to_vec(), ..analyze_lines(lines) };
+    whitespace::split_blocks(boxes, spans, &lines)
 }
 
 pub fn exclude_header_and_footer<'a, E: Encoder>(boxes: &'a mut [(RectF, usize)], bbox: RectF, spans: &[TextSpan<E>]) -> &'a mut [(RectF, usize)]
 {
-    let avg_font_size: f32 = avg(spans.iter().map(|s| s.font_size)).unwrap();
+    let avg_font_size: f32 = avg(spans.iter().map(|s| s.font_size)).unwrap_or(0.0);
 
     let probably_header = |boxes: &[(RectF, usize)]| {
         let class = classify(boxes.iter().filter_map(|&(_, i)| spans.get(i)));
         if matches!(class, Class::Header | Class::Number) {
             return true;
         }
-        let f = avg(boxes.iter().filter_map(|&(_, i)| spans.get(i)).map(|s| s.font_size)).unwrap();
-        f > avg_font_size
+        // figures without text
+        avg(boxes.iter().filter_map(|&(_, i)| spans.get(i)).map(|s| s.font_size))
+            .map_or(false, |f| f > avg_font_size)
     };
     let probably_footer = |boxes: &mut [(RectF, usize)]| {
         sort_x(boxes);
@@ -71,20 +129,46 @@ pub fn exclude_header_and_footer<'a, E: Encoder>(boxes: &'a mut [(RectF, usize)]
             boxes = &mut boxes[top..];
         }
     }
+    boxes
+}
+
+/// Split off the text in the left and right margin: narrow regions separated from the body by a gap.
+/// The gap must be at least twice as wide as a line is high, so hanging bullets and numbers stay with their items.
+/// Returns the left margin, the body and the right margin.
+pub fn split_margins(boxes: &mut [(RectF, usize)], bbox: RectF) -> (&mut [(RectF, usize)], &mut [(RectF, usize)], &mut [(RectF, usize)]) {
     sort_x(boxes);
-    let (left, right) = left_right_gap(boxes, bbox);
-    if let Some(right) = right {
-        if probably_header(&boxes[right..]) {
-            boxes = &mut boxes[..right];
-        }
+    let line_height = median(boxes.iter().map(|(r, _)| r.height()).collect()).unwrap_or(0.0);
+    let (left, right) = left_right_gap(boxes, bbox, 2.0 * line_height);
+    let n = boxes.len();
+    let mut left = left.unwrap_or(0);
+    let mut right = right.unwrap_or(n);
+    // the margins hold less text than the body, and not a line next to most of its lines like the first column of a table
+    let body_rows = |left: usize, right: usize| {
+        let mut rows: Vec<f32> = boxes[left..right].iter().map(|(r, _)| r.center().y()).collect();
+        rows.sort_by(f32::total_cmp);
+        rows.dedup_by(|a, b| (*a - *b).abs() < 0.5 * line_height);
+        rows
+    };
+    let aligned = |side: &[(RectF, usize)], rows: &[f32]| {
+        rows.iter().filter(|&&y| side.iter().any(|(r, _)| (r.center().y() - y).abs() < 0.25 * line_height)).count()
+    };
+    let rows = body_rows(left, right);
+    if 2 * left > right - left || 2 * aligned(&boxes[..left], &rows) > rows.len() {
+        left = 0;
     }
-    if let Some(left) = left {
-        if probably_header(&boxes[..left]) {
-            boxes = &mut boxes[left..];
-        }
+    let rows = body_rows(left, right);
+    if 2 * (n - right) > right - left || 2 * aligned(&boxes[right..], &rows) > rows.len() {
+        right = n;
     }
 
-    boxes
+    let (rest, right) = boxes.split_at_mut(right);
+    let (left, body) = rest.split_at_mut(left);
+    (left, body, right)
+}
+
+fn is_rotated<E: Encoder>(span: &TextSpan<E>) -> bool {
+    let m = span.transform.matrix;
+    m.m12().abs() > m.m11().abs()
 }
 
 
@@ -99,7 +183,12 @@ pub enum Node {
         cells: Vec<Node>,
         tag: NodeTag 
     },
-    Table { table: table::Table<Vec<usize>> },
+    Table {
+        table: table::Table<TableCell>,
+        /// The number of rows at the top that form the header
+        header_rows: usize,
+        header: Vec<HeaderCell<TableCell>>,
+    },
 }
 impl Node {
     pub fn tag(&self) -> NodeTag {
@@ -117,10 +206,10 @@ impl Node {
                     n.indices(out);
                 }
             }
-            Node::Table { ref table } => {
+            Node::Table { ref table, .. } => {
                 out.extend(
                     table.values()
-                        .flat_map(|v| v.value.iter())
+                        .flat_map(|v| v.value.indices.iter())
                         .cloned()
                 );
             }
@@ -145,6 +234,30 @@ fn split<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], lines:
         return Node::singleton(boxes);
     }
 
+    // a table is cut out with the text above and below it, before the gaps between its columns cut it apart
+    if let Some(band) = table::find_table(boxes, spans, lines) {
+        // the rows are apart by at least 0.1
+        let cuts = [band.min_y() - 0.05, band.max_y() + 0.05];
+        sort_y(boxes);
+        let mut parts = split_by(boxes, &cuts, |r| r.min_y());
+        let (above, rows, below) = (parts.next().unwrap(), parts.next().unwrap(), parts.next().unwrap());
+        if above.is_empty() && below.is_empty() {
+            return table::split(rows, spans, lines);
+        }
+        let mut y = vec![];
+        let mut cells = vec![];
+        if !above.is_empty() {
+            y.push(cuts[0]);
+            cells.push(split(above, spans, lines));
+        }
+        cells.push(table::split(rows, spans, lines));
+        if !below.is_empty() {
+            y.push(cuts[1]);
+            cells.push(split(below, spans, lines));
+        }
+        return Node::Grid { x: vec![], y, cells, tag: NodeTag::Complex };
+    }
+
     sort_x(boxes);
     let max_x_gap = dist_x(boxes);
 
@@ -176,11 +289,6 @@ fn split<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], lines:
         return overlapping_lines(boxes);
     }
 
-    //TODO: Disable the table::split for now, because it is not accurate 
-    // if x_gaps.len() > 1 && y_gaps.len() > 1 {
-    //     return table::split(boxes, spans, lines);
-    // }
-
     assert!(
         x_gaps.len() > 0 || y_gaps.len() > 0, 
         "At least one of x_gaps and y_gaps must be non-empty, otherwise the memory will be exhausted"
@@ -263,3 +371,40 @@ impl<'a, I, F> Iterator for SplitBy<'a, I, F> where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_geometry::vector::Vector2F;
+
+    fn boxes(rects: &[(f32, f32, f32)]) -> Vec<(RectF, usize)> {
+        rects.iter().enumerate()
+            .map(|(i, &(x, y, w))| (RectF::from_points(Vector2F::new(x, y), Vector2F::new(x + w, y + 10.0)), i))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_margins() {
+        let page = RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0));
+
+        // hanging bullets with a gap of one and a half lines
+        let mut items = boxes(&[(60.0, 100.0, 5.0), (80.0, 100.0, 400.0), (60.0, 120.0, 5.0), (80.0, 120.0, 400.0)]);
+        let (left, body, right) = split_margins(&mut items, page);
+        assert_eq!((left.len(), body.len(), right.len()), (0, 4, 0));
+
+        // a margin note far from the body
+        let mut noted = boxes(&[(20.0, 100.0, 50.0), (120.0, 100.0, 400.0), (120.0, 120.0, 400.0), (120.0, 140.0, 400.0)]);
+        let (left, body, right) = split_margins(&mut noted, page);
+        assert_eq!((left.len(), body.len(), right.len()), (1, 3, 0));
+
+        // the first column of a table, a line next to every line of the others
+        let mut table = boxes(&[(70.0, 100.0, 40.0), (70.0, 120.0, 40.0), (70.0, 140.0, 40.0), (200.0, 100.0, 300.0), (200.0, 120.0, 300.0), (200.0, 140.0, 300.0)]);
+        let (left, body, right) = split_margins(&mut table, page);
+        assert_eq!((left.len(), body.len(), right.len()), (0, 6, 0));
+
+        // and one on the right
+        let mut noted = boxes(&[(20.0, 100.0, 440.0), (20.0, 120.0, 440.0), (500.0, 100.0, 60.0)]);
+        let (left, body, right) = split_margins(&mut noted, page);
+        assert_eq!((left.len(), body.len(), right.len()), (0, 2, 1));
+    }
+}
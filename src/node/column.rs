@@ -0,0 +1,248 @@
+use font::Encoder;
+use pathfinder_geometry::rect::RectF;
+use pdf_render::TextSpan;
+
+use crate::util::median;
+
+use super::{line::Lines, sort_y, split, table::find_table, Node, NodeTag};
+
+/// Split a multi-column page into blocks and put them in reading order.
+///
+/// Columns are separated by gutters: vertical strips that stay empty over most of the page,
+/// except for elements that span several columns (titles, figures, tables).
+/// Each column between two spanning elements becomes a block, as does each group of spanning elements.
+/// The blocks are split further with the XY cut and ordered by [`reading_order`].
+///
+/// Returns `None` if the page has no gutter.
+pub fn split_columns<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], lines: &Lines) -> Option<Node> {
+    if boxes.len() < 6 {
+        return None;
+    }
+    let line_height = median(boxes.iter().map(|(r, _)| r.height()).collect())?;
+
+    let gutters = gutters(boxes, line_height);
+    if gutters.is_empty() {
+        return None;
+    }
+
+    // a table across a gutter spans the columns as a whole
+    let table = find_table(boxes, spans, lines)
+        .filter(|t| gutters.iter().any(|&(a, b)| t.min_x() < a && t.max_x() > b));
+    let in_table = |r: &RectF| table.map_or(false, |t| t.contains_rect(*r));
+    let crosses_gutter = |r: &RectF| in_table(r) || gutters.iter().any(|&(a, b)| r.min_x() < b && r.max_x() > a);
+
+    // group the spanning elements, each group is a barrier between two sections of columns
+    sort_y(boxes);
+    let mut barriers: Vec<(RectF, Vec<(RectF, usize)>)> = vec![];
+    for &(r, i) in boxes.iter().filter(|(r, _)| crosses_gutter(r)) {
+        match barriers.last_mut() {
+            Some((rect, group)) if r.min_y() < rect.max_y() + 1.5 * line_height || (in_table(&r) && in_table(&group[0].0)) => {
+                *rect = rect.union_rect(r);
+                group.push((r, i));
+            }
+            _ => barriers.push((r, vec![(r, i)])),
+        }
+    }
+
+    // sections[s][c] are the boxes of column c below barrier s-1 and above barrier s
+    let mut sections = vec![vec![vec![]; gutters.len() + 1]; barriers.len() + 1];
+    for &(r, i) in boxes.iter().filter(|(r, _)| !crosses_gutter(r)) {
+        let section = barriers.iter().filter(|(b, _)| b.max_y() <= r.center().y()).count();
+        let column = gutters.iter().filter(|&&(_, b)| b <= r.min_x()).count();
+        sections[section][column].push((r, i));
+    }
+
+    let mut blocks: Vec<(RectF, Node)> = vec![];
+    let groups = sections.into_iter().flatten()
+        .chain(barriers.into_iter().map(|(_, group)| group));
+    for mut group in groups {
+        if let Some(rect) = group.iter().map(|&(r, _)| r).reduce(|a, b| a.union_rect(b)) {
+            blocks.push((rect, split(&mut group, spans, lines)));
+        }
+    }
+
+    let rects: Vec<RectF> = blocks.iter().map(|&(r, _)| r).collect();
+    let mut blocks: Vec<Option<Node>> = blocks.into_iter().map(|(_, n)| Some(n)).collect();
+    let cells = reading_order(&rects).into_iter()
+        .filter_map(|i| blocks[i].take())
+        .collect();
+
+    Some(Node::Grid {
+        x: vec![],
+        y: vec![],
+        cells,
+        tag: NodeTag::Complex,
+    })
+}
+
+/// Find column gutters, as (start, end) x ranges, left to right.
+///
+/// Sums up the height of the boxes over each x position. Within the text of a column that sum is high,
+/// within a gutter it is low: only the spanning elements cross it.
+/// Boxes wider than half the content are left out, a tall table or figure would fill the gutter otherwise.
+/// A gutter must be at least about a line high wide, and have columns of a fair width with at least
+/// three lines on either side.
+fn gutters(boxes: &[(RectF, usize)], line_height: f32) -> Vec<(f32, f32)> {
+    let content = boxes.iter().map(|&(r, _)| r).reduce(|a, b| a.union_rect(b)).unwrap();
+    let x0 = content.min_x().floor();
+    let n = (content.max_x() - x0).ceil().max(1.0) as usize;
+
+    let mut coverage = vec![0.0f32; n];
+    for (r, _) in boxes.iter().filter(|(r, _)| r.width() < 0.5 * content.width()) {
+        let start = ((r.min_x() - x0) as usize).min(n - 1);
+        let end = ((r.max_x() - x0).ceil() as usize).clamp(start + 1, n);
+        for c in coverage[start..end].iter_mut() {
+            *c += r.height();
+        }
+    }
+    let peak = coverage.iter().cloned().fold(0.0, f32::max);
+    let max = |slice: &[f32]| slice.iter().cloned().fold(0.0, f32::max);
+
+    let mut gutters = vec![];
+    let mut start = None;
+    for i in 0..=n {
+        let low = i < n && coverage[i] < 0.25 * peak;
+        match (low, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                start = None;
+                let (a, b) = (x0 + s as f32, x0 + i as f32);
+                let wide_enough = (i - s) as f32 >= 0.8 * line_height;
+                let columns = s as f32 >= 0.15 * n as f32 && (n - i) as f32 >= 0.15 * n as f32;
+                let text_on_both_sides = max(&coverage[..s]) >= 0.5 * peak && max(&coverage[i..]) >= 0.5 * peak;
+                if wide_enough && columns && text_on_both_sides && persists(boxes, a, b, line_height) {
+                    gutters.push((a, b));
+                }
+            }
+            _ => {}
+        }
+    }
+    gutters
+}
+
+/// Whether there are at least three lines of text next to the gutter on either side, at the same height.
+fn persists(boxes: &[(RectF, usize)], a: f32, b: f32, line_height: f32) -> bool {
+    let rows = |side: &dyn Fn(&RectF) -> bool| {
+        let mut centers: Vec<f32> = boxes.iter().filter(|(r, _)| side(r)).map(|(r, _)| r.center().y()).collect();
+        centers.sort_by(f32::total_cmp);
+        centers.windows(2).filter(|w| w[1] - w[0] > 0.5 * line_height).count() + (centers.len() > 0) as usize
+    };
+    let left = |r: &RectF| r.max_x() <= a;
+    let right = |r: &RectF| r.min_x() >= b;
+    let extent = |side: &dyn Fn(&RectF) -> bool| {
+        boxes.iter().filter(|(r, _)| side(r)).map(|&(r, _)| r).reduce(|a, b| a.union_rect(b))
+    };
+    let overlap = match (extent(&left), extent(&right)) {
+        (Some(l), Some(r)) => l.max_y().min(r.max_y()) - l.min_y().max(r.min_y()),
+        _ => return false,
+    };
+    rows(&left) >= 3 && rows(&right) >= 3 && overlap > 2.0 * line_height
+}
+
+/// Order blocks for reading with a topological sort, using the rules of Breuel:
+///
+/// 1. `a` comes before `b` if they overlap horizontally and `a` is above `b`.
+/// 2. `a` comes before `b` if `a` is left of `b` and there is no block `c` between them vertically
+///    that overlaps both horizontally.
+///
+/// Blocks without a predecessor are taken from top to bottom, then left to right.
+pub fn reading_order(rects: &[RectF]) -> Vec<usize> {
+    let n = rects.len();
+    let overlap_x = |a: &RectF, b: &RectF| a.min_x() < b.max_x() && b.min_x() < a.max_x();
+
+    let mut before = vec![vec![false; n]; n];
+    for (i, a) in rects.iter().enumerate() {
+        for (j, b) in rects.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            before[i][j] = if overlap_x(a, b) {
+                a.center().y() < b.center().y()
+            } else if a.max_x() <= b.min_x() {
+                let top = a.max_y().min(b.max_y());
+                let bottom = a.min_y().max(b.min_y());
+                !rects.iter().any(|c| {
+                    top < c.center().y() && c.center().y() < bottom && overlap_x(c, a) && overlap_x(c, b)
+                })
+            } else {
+                false
+            };
+        }
+    }
+
+    let mut done = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    while order.len() < n {
+        let free = |j: usize| (0..n).all(|i| done[i] || !before[i][j]);
+        let key = |&i: &usize| (rects[i].min_y(), rects[i].min_x());
+        let next = (0..n).filter(|&j| !done[j] && free(j))
+            .min_by(|a, b| key(a).partial_cmp(&key(b)).unwrap())
+            // a cycle, should not happen, take the topmost block
+            .or_else(|| (0..n).filter(|&j| !done[j]).min_by(|a, b| key(a).partial_cmp(&key(b)).unwrap()))
+            .unwrap();
+        done[next] = true;
+        order.push(next);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::line::analyze_lines;
+    use pathfinder_geometry::vector::Vector2F;
+    use pdf_render::font::OutlineBuilder;
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> RectF {
+        RectF::from_points(Vector2F::new(x0, y0), Vector2F::new(x1, y1))
+    }
+
+    #[test]
+    fn test_two_columns() {
+        let mut boxes = vec![];
+        let mut line = |r: RectF| {
+            let i = boxes.len();
+            boxes.push((r, i));
+            i
+        };
+        let title = line(rect(150.0, 50.0, 450.0, 70.0));
+        let mut left_top = vec![];
+        let mut right_top = vec![];
+        for k in 0..5 {
+            let y = 100.0 + 12.0 * k as f32;
+            left_top.push(line(rect(50.0, y, 280.0, y + 10.0)));
+            right_top.push(line(rect(320.0, y, 550.0, y + 10.0)));
+        }
+        let figure = line(rect(50.0, 200.0, 550.0, 300.0));
+        let mut left_bottom = vec![];
+        let mut right_bottom = vec![];
+        for k in 0..5 {
+            let y = 320.0 + 12.0 * k as f32;
+            left_bottom.push(line(rect(50.0, y, 280.0, y + 10.0)));
+            right_bottom.push(line(rect(320.0, y, 550.0, y + 10.0)));
+        }
+
+        let spans: &[TextSpan<OutlineBuilder>] = &[];
+        let node = split_columns(&mut boxes, spans, &analyze_lines(&[])).unwrap();
+        let mut indices = vec![];
+        node.indices(&mut indices);
+
+        let expected: Vec<usize> = std::iter::once(title)
+            .chain(left_top)
+            .chain(right_top)
+            .chain(std::iter::once(figure))
+            .chain(left_bottom)
+            .chain(right_bottom)
+            .collect();
+        assert_eq!(indices, expected);
+    }
+
+    #[test]
+    fn test_single_column() {
+        let mut boxes: Vec<(RectF, usize)> = (0..10)
+            .map(|k| (rect(50.0, 100.0 + 12.0 * k as f32, 550.0, 110.0 + 12.0 * k as f32), k))
+            .collect();
+        let spans: &[TextSpan<OutlineBuilder>] = &[];
+        assert!(split_columns(&mut boxes, spans, &analyze_lines(&[])).is_none());
+    }
+}
@@ -78,23 +78,26 @@ pub fn top_bottom_gap(boxes: &mut [(RectF, usize)], bbox: RectF) -> (Option<usiz
     }
 }
 
-pub fn left_right_gap(boxes: &mut [(RectF, usize)], bbox: RectF) -> (Option<usize>, Option<usize>) {
+/// Find the gaps of at least `min_gap` that split off narrow margins on the left and right:
+/// the text left of the left gap and right of the right gap lies in the outer fifth of the page.
+pub fn left_right_gap(boxes: &mut [(RectF, usize)], bbox: RectF, min_gap: f32) -> (Option<usize>, Option<usize>) {
     let num_boxes = boxes.len();
     if num_boxes < 2 {
         return (None, None);
     }
 
-    let mut gaps = gap_list(boxes, |r| (r.min_x(), r.max_x()));
+    let mut gaps = gap_list(boxes, |r| (r.min_x(), r.max_x()))
+        .filter(|&(start, end, _)| end - start >= min_gap);
     let left_limit = bbox.min_x() + bbox.width() * 0.2;
     let right_limit = bbox.min_x() + bbox.width() * 0.8;
     match gaps.next() {
         Some((x, _, left)) if x < left_limit  => {
             match gaps.last() {
-                Some((x, _, right)) if x > right_limit => (Some(left), Some(right)),
+                Some((_, x, right)) if x > right_limit => (Some(left), Some(right)),
                 _ => (Some(left), None)
             }
         }
-        Some((x, _, right)) if x > right_limit => (None, Some(right)),
+        Some((_, x, right)) if x > right_limit => (None, Some(right)),
         _ => (None, None)
     }
 }
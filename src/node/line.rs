@@ -1,21 +1,26 @@
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use ordered_float::NotNan;
-use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::{rect::RectF, vector::Vector2F};
+use pdf_render::Fill;
 
-use crate::util::avg;
+use crate::util::{avg, root};
 
 use super::{sort_x, sort_y, Node, NodeTag};
 
 pub fn analyze_lines(lines: &[[f32; 4]]) -> Lines {
     let mut hlines = BTreeSet::new();
     let mut vlines = BTreeSet::new();
+    let mut hsegments = vec![];
+    let mut vsegments = vec![];
 
     for &[x1, y1, x2, y2] in lines {
         if x1 == x2 {
             vlines.insert(NotNan::new(x1).unwrap());
+            vsegments.push((x1, y1.min(y2), y1.max(y2)));
         } else if y1 == y2 {
             hlines.insert(NotNan::new(y1).unwrap());
+            hsegments.push((y1, x1.min(x2), x1.max(x2)));
         }
     }
 
@@ -47,7 +52,7 @@ pub fn analyze_lines(lines: &[[f32; 4]]) -> Lines {
     //println!("hlines: {:?}", hlines);
     //println!("vlines: {:?}", vlines);
 
-    Lines { hlines, vlines, line_grid }
+    Lines { hlines, vlines, line_grid, hsegments, vsegments, fills: vec![] }
 }
 
 /// Group lines that are consecutive within a distance of 10.0.
@@ -75,6 +80,90 @@ pub struct Lines {
     pub hlines: Vec<(f32, f32)>,
     pub vlines: Vec<(f32, f32)>,
     pub line_grid: Vec<bool>,
+    /// The horizontal segments as `(y, min_x, max_x)`, to tell in which columns a rule separates two rows.
+    pub hsegments: Vec<(f32, f32, f32)>,
+    /// The vertical segments as `(x, min_y, max_y)`.
+    pub vsegments: Vec<(f32, f32, f32)>,
+    /// The filled boxes behind text with their colour, like the shaded rows of a table. Their edges are among the rules.
+    pub fills: Vec<(RectF, Fill)>,
+}
+impl Lines {
+    /// The rules of a grid fully inside `rect`, like a small table in a cell of a larger one.
+    ///
+    /// It takes at least two horizontal and two vertical rules inside the rect, each crossing or touching
+    /// two of the other direction. The rules of `rect` itself are not part of it.
+    pub fn inner_grid(&self, rect: RectF) -> Option<Lines> {
+        let tolerance = 1.0;
+        let inner = rect.contract(2.0 * tolerance);
+        let hsegments: Vec<(f32, f32, f32)> = self.hsegments.iter().cloned()
+            .filter(|&(y, x1, x2)| inner.min_y() < y && y < inner.max_y() && rect.min_x() - tolerance <= x1 && x2 <= rect.max_x() + tolerance)
+            .collect();
+        let vsegments: Vec<(f32, f32, f32)> = self.vsegments.iter().cloned()
+            .filter(|&(x, y1, y2)| inner.min_x() < x && x < inner.max_x() && rect.min_y() - tolerance <= y1 && y2 <= rect.max_y() + tolerance)
+            .collect();
+
+        let crosses = |(y, x1, x2): (f32, f32, f32), (x, y1, y2): (f32, f32, f32)| {
+            x1 - tolerance <= x && x <= x2 + tolerance && y1 - tolerance <= y && y <= y2 + tolerance
+        };
+        let hsegments: Vec<_> = hsegments.iter().cloned().filter(|&h| vsegments.iter().filter(|&&v| crosses(h, v)).count() >= 2).collect();
+        let vsegments: Vec<_> = vsegments.iter().cloned().filter(|&v| hsegments.iter().filter(|&&h| crosses(h, v)).count() >= 2).collect();
+        if hsegments.len() < 2 || vsegments.len() < 2 {
+            return None;
+        }
+
+        let lines: Vec<[f32; 4]> = hsegments.iter().map(|&(y, x1, x2)| [x1, y, x2, y])
+            .chain(vsegments.iter().map(|&(x, y1, y2)| [x, y1, x, y2]))
+            .collect();
+        Some(analyze_lines(&lines))
+    }
+
+    /// Take out the grids nested in a cell of a larger one, so they don't split its rows and columns.
+    /// Returns the remaining rules and the bounds of the nested grids.
+    ///
+    /// Rules that cross or touch form a group. A group of at least two horizontal and two vertical rules
+    /// that lies inside the bounds of another group without touching it is nested in that one.
+    pub fn split_inner_grids(&self) -> (Lines, Vec<RectF>) {
+        let tolerance = 1.0;
+        let crosses = |(y, x1, x2): (f32, f32, f32), (x, y1, y2): (f32, f32, f32)| {
+            x1 - tolerance <= x && x <= x2 + tolerance && y1 - tolerance <= y && y <= y2 + tolerance
+        };
+
+        // union-find over the horizontal segments followed by the vertical ones
+        let n_h = self.hsegments.len();
+        let mut parent: Vec<usize> = (0 .. n_h + self.vsegments.len()).collect();
+        for (h, &hs) in self.hsegments.iter().enumerate() {
+            for (v, &vs) in self.vsegments.iter().enumerate() {
+                if crosses(hs, vs) {
+                    let (a, b) = (root(&mut parent, h), root(&mut parent, n_h + v));
+                    parent[a] = b;
+                }
+            }
+        }
+
+        let rects = self.hsegments.iter().map(|&(y, x1, x2)| RectF::from_points(Vector2F::new(x1, y), Vector2F::new(x2, y)))
+            .chain(self.vsegments.iter().map(|&(x, y1, y2)| RectF::from_points(Vector2F::new(x, y1), Vector2F::new(x, y2))));
+        // per group: bounds, horizontal and vertical count
+        let mut groups: HashMap<usize, (RectF, usize, usize)> = HashMap::new();
+        for (i, rect) in rects.enumerate() {
+            let (h, v) = if i < n_h { (1, 0) } else { (0, 1) };
+            let group = groups.entry(root(&mut parent, i)).or_insert((rect, 0, 0));
+            group.0 = group.0.union_rect(rect);
+            group.1 += h;
+            group.2 += v;
+        }
+        let nested: Vec<(usize, RectF)> = groups.iter()
+            .filter(|&(_, &(rect, h, v))| h >= 2 && v >= 2 && groups.values().any(|&(outer, _, _)| outer.contract(tolerance).contains_rect(rect)))
+            .map(|(&g, &(rect, _, _))| (g, rect))
+            .collect();
+
+        let lines: Vec<[f32; 4]> = self.hsegments.iter().map(|&(y, x1, x2)| [x1, y, x2, y])
+            .chain(self.vsegments.iter().map(|&(x, y1, y2)| [x, y1, x, y2]))
+            .enumerate()
+            .filter(|&(i, _)| { let g = root(&mut parent, i); !nested.iter().any(|&(n, _)| n == g) })
+            .map(|(_, l)| l)
+            .collect();
+        (Lines { fills: self.fills.clone(), ..analyze_lines(&lines) }, nested.into_iter().map(|(_, rect)| rect).collect())
+    }
 }
 
 /// Deals with things like superscript and subscript, which fall outside the usual bounds 
@@ -156,4 +245,37 @@ mod tests {
         // Assert that the result matches the expected output
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_inner_grid() {
+        use pathfinder_geometry::vector::Vector2F;
+
+        // an outer cell from (0, 0) to (200, 100), with a 2x2 grid from (20, 40) to (120, 80) in it
+        let mut lines = vec![
+            [0.0, 0.0, 200.0, 0.0], [0.0, 100.0, 200.0, 100.0],
+            [0.0, 0.0, 0.0, 100.0], [200.0, 0.0, 200.0, 100.0],
+        ];
+        for y in [40.0, 60.0, 80.0] {
+            lines.push([20.0, y, 120.0, y]);
+        }
+        for x in [20.0, 70.0, 120.0] {
+            lines.push([x, 40.0, x, 80.0]);
+        }
+        let lines = analyze_lines(&lines);
+        let cell = RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(200.0, 100.0));
+        let inner = lines.inner_grid(cell).unwrap();
+        assert_eq!(inner.hsegments.len(), 3);
+        assert_eq!(inner.vsegments.len(), 3);
+
+        // the outer grid keeps only the rules of the cell
+        let (outer, nested) = lines.split_inner_grids();
+        assert_eq!(outer.hsegments.len(), 2);
+        assert_eq!(outer.vsegments.len(), 2);
+        assert_eq!(outer.hlines.len(), 2);
+        assert_eq!(nested, [RectF::from_points(Vector2F::new(20.0, 40.0), Vector2F::new(120.0, 80.0))]);
+
+        // a single rule, like an underline for a signature, is no grid
+        let lines = analyze_lines(&[[20.0, 50.0, 120.0, 50.0]]);
+        assert!(lines.inner_grid(cell).is_none());
+    }
 }
\ No newline at end of file
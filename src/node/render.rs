@@ -31,12 +31,11 @@ fn _render<E: Encoder>(w: &mut String, spans: &[TextSpan<E>], node: &Node, bbox:
             if indices.len() > 0 {
                 let class = classify(indices.iter().cloned().filter_map(|i| spans.get(i)));
 
-                for &i in indices.iter() {
-                    let r = spans[i].rect;
+                for r in indices.iter().filter_map(|&i| spans.get(i)).map(|s| s.rect) {
                     write!(w, r#"<line x1="{}" x2="{}" y1="{}" y2="{}" class="{:?}" />"#,
                         r.min_x(), r.max_x(), r.max_y(), r.max_y(),
                         class
-                    );
+                    ).unwrap();
                 }
             }
         }
@@ -45,18 +44,28 @@ fn _render<E: Encoder>(w: &mut String, spans: &[TextSpan<E>], node: &Node, bbox:
             let columns = x.len() + 1;
             write!(w, r#"<rect x="{}" y="{}" width="{}" height="{}" class="{:?}" />"#,
                 bbox.min_x(), bbox.min_y(), bbox.width(), bbox.height(), tag
-            );
+            ).unwrap();
+
+            // the cells are not laid out on a grid, e.g. the blocks of a page with columns
+            if cells.len() != columns * (y.len() + 1) {
+                for cell in cells {
+                    if let Some(bbox) = span_bbox(spans, cell) {
+                        _render(w, spans, cell, bbox, level+1);
+                    }
+                }
+                return;
+            }
 
             for (j, ((min_y, max_y), row)) in once(bbox.min_y()).chain(y.iter().cloned()).chain(once(bbox.max_y())).tuple_windows().zip(cells.chunks_exact(columns)).enumerate() {
                 if j > 0 {
-                    writeln!(w, r#"<line x1="{}" x2="{}" y1="{}" y2="{}" level="{level}"></line>"#,
-                        bbox.min_x(), bbox.max_x(), min_y, min_y);
+                    writeln!(w, r#"<line x1="{}" x2="{}" y1="{}" y2="{}" class="gap" level="{level}"></line>"#,
+                        bbox.min_x(), bbox.max_x(), min_y, min_y).unwrap();
                 }
 
                 for (i, ((min_x, max_x), cell)) in once(bbox.min_x()).chain(x.iter().cloned()).chain(once(bbox.max_x())).tuple_windows().zip(row).enumerate() {
                     if i > 0 {
-                        writeln!(w, r#"<line x1="{}" x2="{}" y1="{}" y2="{}" level="{level}"></line>"#,
-                            min_x, min_x, bbox.min_y(), bbox.max_y());
+                        writeln!(w, r#"<line x1="{}" x2="{}" y1="{}" y2="{}" class="gap" level="{level}"></line>"#,
+                            min_x, min_x, bbox.min_y(), bbox.max_y()).unwrap();
                     }
 
                     let bbox = RectF::from_points(Vector2F::new(min_x, min_y), Vector2F::new(max_x, max_y));
@@ -65,7 +74,15 @@ fn _render<E: Encoder>(w: &mut String, spans: &[TextSpan<E>], node: &Node, bbox:
             }
         }
         Node::Table { .. } => {
-            
+            write!(w, r#"<rect x="{}" y="{}" width="{}" height="{}" class="Table" />"#,
+                bbox.min_x(), bbox.min_y(), bbox.width(), bbox.height()
+            ).unwrap();
         }
     }
 }
+
+fn span_bbox<E: Encoder>(spans: &[TextSpan<E>], node: &Node) -> Option<RectF> {
+    let mut indices = vec![];
+    node.indices(&mut indices);
+    indices.iter().filter_map(|&i| spans.get(i)).map(|s| s.rect).reduce(|a, b| a.union_rect(b))
+}
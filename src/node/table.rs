@@ -1,19 +1,64 @@
 use font::Encoder;
-use pathfinder_geometry::rect::RectF;
-use pdf_render::TextSpan;
+use std::{cmp::Reverse, collections::HashMap, iter::once, mem::take};
+use pathfinder_geometry::{rect::RectF, vector::Vector2F};
+use pdf_render::{Fill, TextSpan};
 use itertools::Itertools;
 use ordered_float::NotNan;
-use crate::{node::{sort_x, sort_y, NodeTag}, util::avg};
+use serde::{Serialize, Deserialize};
+use crate::{classify::{classify, Class}, node::{sort_x, sort_y, NodeTag}, util::{avg, median}, value::{self, Value}};
 use super::{gap::{dist_y, gaps}, line::Lines, split_by, Node};
 
 pub use table::Table;
 
-pub fn split<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], lines_info: &Lines) -> Node {
+/// A cell of a table: the spans in it and its place in the grid.
+#[derive(Debug)]
+pub struct TableCell {
+    pub row: usize,
+    pub col: usize,
+    pub rowspan: usize,
+    pub colspan: usize,
+    /// The boundaries of the cell in the grid, rather than of its text
+    pub rect: RectF,
+    pub indices: Vec<usize>,
+    /// The layout of the spans of the cell, if it has a structure of its own like a table inside the cell
+    pub nested: Option<Box<Node>>,
+}
+impl TableCell {
+    fn new(row: usize, cell: &Cell, grid: &Grid) -> TableCell {
+        let colspan = cell.last_col - cell.first_col + 1;
+        TableCell {
+            row,
+            col: cell.first_col,
+            rowspan: cell.rowspan,
+            colspan,
+            rect: grid.rect(row, cell.first_col, cell.rowspan, colspan),
+            indices: cell.indices.clone(),
+            nested: None,
+        }
+    }
+}
+
+/// The layout of a cell that has a grid of rules inside, like a small table in a cell of a form.
+fn nested<E: Encoder>(cell: &TableCell, boxes: &[(RectF, usize)], spans: &[TextSpan<E>], lines: &Lines) -> Option<Box<Node>> {
+    let inner = lines.inner_grid(cell.rect)?;
+    let mut cell_boxes: Vec<(RectF, usize)> = boxes.iter().cloned().filter(|b| cell.indices.contains(&b.1)).collect();
+    if cell_boxes.len() < 2 {
+        return None;
+    }
+    Some(Box::new(split(&mut cell_boxes, spans, &inner)))
+}
+
+pub fn split<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], all_lines: &Lines) -> Node {
+    // a grid inside a cell is left to the layout of that cell
+    let (lines_info, inner_grids) = all_lines.split_inner_grids();
+    let lines_info = &lines_info;
+    let grid_of = |x: Span, y: Span| inner_grids.iter().position(|g| {
+        g.contract(-1.0).contains_rect(RectF::from_points(Vector2F::new(*x.start, *y.start), Vector2F::new(*x.end, *y.end)))
+    });
     sort_y(boxes);
     let mut lines = vec![];
     let mut y = Span::vert(&boxes[0].0).unwrap();
-    let mut items = vec![boxes[0]];
-    
+
     let mut line = vec![boxes[0]];
     for &(rect, i) in &boxes[1..] {
         let y2 = Span::vert(&rect).unwrap();
@@ -30,10 +75,24 @@ pub fn split<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], li
     sort_x(&mut line);
     lines.push(build_line(&line, spans));
 
+    // the text in one inner grid is one part of the line, as wide as the grid
+    for (_, y, parts) in lines.iter_mut() {
+        *parts = take(parts).into_iter()
+            .coalesce(|a, b| match (grid_of(a.0, *y), grid_of(b.0, *y)) {
+                (Some(g), Some(h)) if g == h => Ok((a.0.hull(b.0), [a.1, b.1].concat())),
+                _ => Err((a, b)),
+            })
+            .map(|(x, indices)| match grid_of(x, *y).and_then(|g| Span::horiz(&inner_grids[g])) {
+                Some(grid) => (x.hull(grid), indices),
+                None => (x, indices),
+            })
+            .collect();
+    }
+    let line_grid = |y: Span, line: &[(Span, Vec<usize>)]| line.iter().find_map(|&(x, _)| grid_of(x, y));
 
     let mut vparts = vec![];
     let mut start = 0;
-    while let Some(p) = lines[start..].iter().position(|(tag, _, line)| matches!(tag, LineTag::Unknown | LineTag::Table)) {
+    while let Some(p) = lines[start..].iter().position(|(tag, _, _)| matches!(tag, LineTag::Unknown | LineTag::Table)) {
         let table_start = start + p;
         let table_end = lines[table_start+1..].iter().position(|(tag, _, _)| matches!(tag, LineTag::Text)).map(|e| table_start+1+e).unwrap_or(lines.len());
         
@@ -44,71 +103,65 @@ pub fn split<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], li
         let lines = &lines[table_start..table_end];
         start = table_end;
 
-        let mut columns: Vec<Span> = vec![];
-        for (_, _, line) in lines.iter() {
-            for &(x, ref parts) in line.iter() {
-                // find any column that is contained in this
-                let mut found = 0;
-                for span in columns.iter_mut() {
-                    if let Some(overlap) = span.intersect(x) {
-                        *span = overlap;
-                        found += 1;
-                    }
-                }
-                if found == 0 {
-                    columns.push(x);
-                }
-            }
-        }
+        let (columns, number_columns) = build_columns(lines, spans);
         let avg_vgap = avg(lines.iter().map(|(_, y, _)| y).tuple_windows().map(|(a, b)| *(b.start - a.end)));
 
-        columns.sort_by_key(|s| s.start);
-
-        let mut buf = String::new();
-
         let d_threshold = avg_vgap.unwrap_or(0.0);
         let mut prev_end = None;
+        let mut prev_grid = None;
 
-        let mut table: Table<Vec<usize>> = Table::empty(lines.len() as u32, columns.len() as u32);
-
-        let mut row = 0;
-        for (_, span, line) in lines {
-            let mut col = 0;
-            
+        let mut rows: Vec<Row> = vec![];
+        for (line_nr, (_, span, line)) in lines.iter().enumerate() {
+            let grid = line_grid(*span, line);
             let combine = prev_end.map(|y: NotNan<f32>| {
                 if *(span.start - y) < d_threshold {
                     !lines_info.hlines.iter().map(|(a, b)| 0.5 * (a+b)).any(|l| *y < l && *span.start > l)
                 } else {
                     false
                 }
-            }).unwrap_or(false);
+            }).unwrap_or(false) || (grid.is_some() && grid == prev_grid);
 
-            if !combine {
-                row += 1;
+            match rows.last_mut() {
+                Some(row) if combine => row.y = row.y.hull(*span),
+                _ => rows.push(Row { y: *span, cells: vec![] }),
             }
+            let row = rows.last_mut().unwrap();
 
-            for &(x, ref parts) in line {
-                let mut cols = columns.iter().enumerate()
-                    .filter(|&(_, &x2)| x.intersect(x2).is_some())
-                    .map(|(i, _)| i);
+            for (part_nr, &(x, ref parts)) in line.iter().enumerate() {
+                let (first_col, last_col) = match number_columns.get(&(line_nr, part_nr)) {
+                    Some(&col) => (col, col),
+                    None => part_columns(x, &columns),
+                };
 
-                let first_col = cols.next().unwrap();
-                let last_col = cols.last().unwrap_or(first_col);
-
-                if let Some(cell) = combine.then(|| table.get_cell_value_mut(row, first_col as u32)).flatten() {
+                match row.cells.iter_mut().find(|c| combine && c.first_col == first_col) {
                     // append to previous line
-                    cell.extend_from_slice(parts);
-                } else {
-                    let colspan = (last_col - first_col) as u32 + 1;
-                    let rowspan = 1;
-                    table.set_cell(parts.clone(), row, first_col as u32, rowspan, colspan);
+                    Some(cell) => {
+                        cell.indices.extend_from_slice(parts);
+                        cell.x = cell.x.hull(x);
+                        cell.y = cell.y.hull(*span);
+                    }
+                    None => row.cells.push(Cell { first_col, last_col, x, y: *span, indices: parts.clone(), rowspan: 1 }),
                 }
-                col = last_col + 1;
             }
             prev_end = Some(span.end);
+            prev_grid = grid;
+        }
+        let rows = rowspans(rows, &columns, &lines_info.hsegments);
+        let header_rows = header_rows(&rows, spans, &lines_info.hsegments, &lines_info.fills);
+        let grid = Grid::new(&rows, &columns, lines_info);
+        let header = header_tree(&rows[..header_rows], &grid);
+
+        let mut table: Table<TableCell> = Table::empty(rows.len() as u32, columns.len() as u32);
+        for (r, row) in rows.iter().enumerate() {
+            for cell in row.cells.iter() {
+                let mut cell = TableCell::new(r, cell, &grid);
+                cell.nested = nested(&cell, boxes, spans, all_lines);
+                let (row, col, rowspan, colspan) = (cell.row as u32, cell.col as u32, cell.rowspan as u32, cell.colspan as u32);
+                table.set_cell(cell, row, col, rowspan, colspan);
+            }
         }
         let y = Span { start: lines[0].1.start, end: lines.last().unwrap().1.end };
-        vparts.push((y, Node::Table { table }));
+        vparts.push((y, Node::Table { table, header_rows, header }));
     }
     for &(_, y, ref line) in &lines[start..] {
         vparts.push((y, Node::Final { indices: line.iter().flat_map(|(_, indices)| indices.iter().cloned()).collect() }));
@@ -127,6 +180,529 @@ pub fn split<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], li
     }
 }
 
+/// Find a table among the boxes, returns the bounds of its boxes.
+///
+/// A table is a band of at least three consecutive rows with text in two or more parts, apart by a line height or more,
+/// and a lone part between two such rows, like the label of a section of the table.
+/// Cut at the gaps between the parts of those rows, either rules run between the rows or columns,
+/// or a column after the first one holds numbers in most of its rows, which text never does.
+/// Columns of running text, their lines filling most of the column, are no table, ruled or not.
+pub fn find_table<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], lines: &Lines) -> Option<RectF> {
+    if boxes.len() < 6 {
+        return None;
+    }
+    let line_height = median(boxes.iter().map(|(r, _)| r.height()).collect())?;
+    sort_y(boxes);
+    let y_gaps: Vec<f32> = gaps(0.1, boxes, |r| (r.min_y(), r.max_y())).collect();
+
+    // the rows with whether they have several parts
+    let mut rows: Vec<(Vec<(RectF, usize)>, bool)> = vec![];
+    for row in split_by(boxes, &y_gaps, |r| r.min_y()) {
+        sort_x(row);
+        let parts = gaps(line_height, row, |r| (r.min_x(), r.max_x())).count() + 1;
+        rows.push((row.to_vec(), parts > 1));
+    }
+
+    let mut start = 0;
+    while start < rows.len() {
+        if !rows[start].1 {
+            start += 1;
+            continue;
+        }
+        let mut end = start + 1;
+        while end < rows.len() && (rows[end].1 || (end + 1 < rows.len() && rows[end + 1].1)) {
+            end += 1;
+        }
+        let band = &rows[start..end];
+        if band.iter().filter(|r| r.1).count() >= 3 && is_table(band, spans, lines, line_height) {
+            return band.iter().flat_map(|r| r.0.iter()).map(|&(r, _)| r).reduce(|a, b| a.union_rect(b));
+        }
+        start = end;
+    }
+    None
+}
+
+fn is_table<E: Encoder>(rows: &[(Vec<(RectF, usize)>, bool)], spans: &[TextSpan<E>], lines: &Lines, line_height: f32) -> bool {
+    let mut boxes: Vec<(RectF, usize)> = rows.iter().flat_map(|r| r.0.iter().cloned()).collect();
+    let bbox = boxes.iter().map(|&(r, _)| r).reduce(|a, b| a.union_rect(b)).unwrap();
+    sort_x(&mut boxes);
+    let x_gaps: Vec<f32> = gaps(line_height, &boxes, |r| (r.min_x(), r.max_x())).collect();
+    if x_gaps.is_empty() {
+        return false;
+    }
+
+    let inside = bbox.contract(1.0);
+    let ruled = lines.hsegments.iter().any(|&(y, x1, x2)| {
+        inside.min_y() < y && y < inside.max_y() && x2.min(bbox.max_x()) - x1.max(bbox.min_x()) >= 0.5 * bbox.width()
+    }) || lines.vsegments.iter().any(|&(x, y1, y2)| {
+        inside.min_x() < x && x < inside.max_x() && y2.min(bbox.max_y()) - y1.max(bbox.min_y()) >= 0.5 * bbox.height()
+    });
+
+    // per column: the rows it has text in, and those that are numbers
+    let column = |r: &RectF| x_gaps.iter().filter(|&&x| x < r.min_x()).count();
+    let mut cells = vec![(0, 0); x_gaps.len() + 1];
+    // per column: the horizontal extent of its text in each row
+    let mut extents = vec![vec![]; x_gaps.len() + 1];
+    for (row, _) in rows {
+        for c in row.iter().map(|(r, _)| column(r)).unique() {
+            cells[c].0 += 1;
+            if classify(row.iter().filter(|(r, _)| column(r) == c).filter_map(|&(_, i)| spans.get(i))) == Class::Number {
+                cells[c].1 += 1;
+            }
+            let in_column = || row.iter().filter(|(r, _)| column(r) == c);
+            let x1 = in_column().map(|(r, _)| r.min_x()).fold(f32::INFINITY, f32::min);
+            let x2 = in_column().map(|(r, _)| r.max_x()).fold(f32::NEG_INFINITY, f32::max);
+            extents[c].push((x1, x2));
+        }
+    }
+
+    // columns of running text, most of their lines fill the column and that is many words wide
+    let running_text = extents.iter().all(|e| {
+        let x1 = e.iter().map(|&(x1, _)| x1).fold(f32::INFINITY, f32::min);
+        let x2 = e.iter().map(|&(_, x2)| x2).fold(f32::NEG_INFINITY, f32::max);
+        let full = e.iter().filter(|&&(a, b)| b - a >= 0.9 * (x2 - x1)).count();
+        x2 - x1 >= 10.0 * line_height && 3 * full >= 2 * e.len()
+    });
+    if running_text {
+        return false;
+    }
+
+    let numbers = cells[1..].iter().any(|&(filled, numbers)| numbers >= 2 && 2 * numbers > filled);
+    ruled || numbers
+}
+
+/// The x positions a number can line up on with the others in its column: the decimal separator
+/// (the end of the digits for whole numbers), the last digit, so that closing parentheses and
+/// percent signs stick out, and the left edge. `None` if the part is no number.
+fn anchors<E: Encoder>(x: Span, parts: &[usize], spans: &[TextSpan<E>]) -> Option<[f32; 3]> {
+    let part_spans: Vec<&TextSpan<E>> = parts.iter().filter_map(|&i| spans.get(i)).collect();
+    let text = part_spans.iter().map(|s| s.text.as_str()).join(" ");
+    if !matches!(value::parse(&text)?, Value::Number(_) | Value::Percentage(_) | Value::Currency { .. }) {
+        return None;
+    }
+    let digits: String = text.chars().filter(|&c| c.is_ascii_digit() || c == '.' || c == ',').collect();
+    let separator = value::decimal_separator(&digits);
+
+    // each char with its range in device space
+    let mut chars = vec![];
+    for s in part_spans {
+        let ends = s.chars.iter().skip(1).map(|c| c.offset).chain(once(s.text.len()));
+        for (c, end) in s.chars.iter().zip(ends) {
+            if let Some(ch) = s.text.get(c.offset..end).and_then(|t| t.chars().next()) {
+                let a = (s.transform * Vector2F::new(c.pos, 0.0)).x();
+                let b = (s.transform * Vector2F::new(c.pos + c.width, 0.0)).x();
+                chars.push((ch, a.min(b), a.max(b)));
+            }
+        }
+    }
+    let right = chars.iter().filter(|c| c.0.is_ascii_digit()).last()?.2;
+    let decimal = separator
+        .and_then(|sep| chars.iter().filter(|c| c.0 == sep).last())
+        .map_or(right, |c| c.1);
+    Some([decimal, right, *x.start])
+}
+
+/// The columns of a table, and the column of each number that lines up with others, by line and part.
+///
+/// Numbers that line up on the same anchor in at least two lines form a column as wide as all of them,
+/// so right aligned numbers of different length and negatives in parentheses stay together.
+/// The other parts form columns where they overlap, except for those on top of a column of numbers, like its header.
+fn build_columns<E: Encoder>(lines: &[(LineTag, Span, Vec<(Span, Vec<usize>)>)], spans: &[TextSpan<E>]) -> (Vec<Span>, HashMap<(usize, usize), usize>) {
+    let font_size = avg(lines.iter()
+        .flat_map(|l| l.2.iter().flat_map(|p| p.1.iter()))
+        .filter_map(|&i| spans.get(i))
+        .map(|s| s.font_size)
+    ).unwrap_or(10.0);
+    let tolerance = 0.25 * font_size;
+
+    let numbers: Vec<((usize, usize), Span, [f32; 3])> = lines.iter().enumerate()
+        .flat_map(|(l, line)| line.2.iter().enumerate().map(move |(p, part)| ((l, p), part)))
+        .filter_map(|(key, &(x, ref parts))| anchors(x, parts, spans).map(|a| (key, x, a)))
+        .collect();
+
+    // the numbers that share an anchor, for every kind of anchor
+    let mut clusters: Vec<Vec<usize>> = vec![];
+    for kind in 0 .. 3 {
+        let mut order: Vec<usize> = (0 .. numbers.len()).collect();
+        order.sort_by(|&a, &b| numbers[a].2[kind].partial_cmp(&numbers[b].2[kind]).unwrap());
+        let mut cluster: Vec<usize> = vec![];
+        for i in order {
+            if let Some(&last) = cluster.last() {
+                if numbers[i].2[kind] - numbers[last].2[kind] > tolerance {
+                    clusters.push(take(&mut cluster));
+                }
+            }
+            cluster.push(i);
+        }
+        clusters.push(cluster);
+    }
+    // the largest first, the decimal separator wins a tie
+    clusters.sort_by_key(|c| Reverse(c.len()));
+
+    let mut assigned: Vec<Option<usize>> = vec![None; numbers.len()];
+    let mut spans_of_numbers: Vec<Span> = vec![];
+    for cluster in clusters {
+        let members: Vec<usize> = cluster.into_iter().filter(|&i| assigned[i].is_none()).collect();
+        if members.iter().map(|&i| numbers[i].0.0).unique().count() < 2 {
+            continue;
+        }
+        let span = members.iter().map(|&i| numbers[i].1).reduce(Span::hull).unwrap();
+        for &i in members.iter() {
+            assigned[i] = Some(spans_of_numbers.len());
+        }
+        spans_of_numbers.push(span);
+    }
+
+    // columns of numbers that reach into each other are one
+    let mut order: Vec<usize> = (0 .. spans_of_numbers.len()).collect();
+    order.sort_by_key(|&c| spans_of_numbers[c].start);
+    let mut number_columns: Vec<Span> = vec![];
+    let mut merged_into = vec![0; spans_of_numbers.len()];
+    for c in order {
+        let span = spans_of_numbers[c];
+        match number_columns.last_mut() {
+            Some(last) if last.intersect(span).is_some() => *last = last.hull(span),
+            _ => number_columns.push(span),
+        }
+        merged_into[c] = number_columns.len() - 1;
+    }
+
+    let mut text_columns: Vec<Span> = vec![];
+    for (l, line) in lines.iter().enumerate() {
+        for (p, &(x, _)) in line.2.iter().enumerate() {
+            if numbers.iter().zip(assigned.iter()).any(|(n, a)| n.0 == (l, p) && a.is_some()) {
+                continue;
+            }
+            // find any column that is contained in this
+            let mut found = 0;
+            for span in text_columns.iter_mut() {
+                if let Some(overlap) = span.intersect(x) {
+                    *span = overlap;
+                    found += 1;
+                }
+            }
+            if found == 0 {
+                text_columns.push(x);
+            }
+        }
+    }
+    text_columns.retain(|&x| !number_columns.iter().any(|&c| covers(x, c)));
+
+    let mut columns: Vec<(Span, Option<usize>)> = text_columns.into_iter().map(|c| (c, None))
+        .chain(number_columns.iter().enumerate().map(|(i, &c)| (c, Some(i))))
+        .collect();
+    columns.sort_by_key(|c| c.0.start);
+
+    let mut by_part = HashMap::new();
+    for (n, a) in numbers.iter().zip(assigned.iter()) {
+        if let Some(a) = *a {
+            let col = columns.iter().position(|c| c.1 == Some(merged_into[a])).unwrap();
+            by_part.insert(n.0, col);
+        }
+    }
+    (columns.into_iter().map(|c| c.0).collect(), by_part)
+}
+
+/// The length of the overlap of the two spans, negative if they are apart.
+fn overlap(a: Span, b: Span) -> f32 {
+    *(a.end.min(b.end) - a.start.max(b.start))
+}
+
+/// Whether a part is in a column: they overlap by at least half of the narrower of the two.
+fn covers(x: Span, column: Span) -> bool {
+    let width = |s: Span| *(s.end - s.start);
+    overlap(x, column) >= 0.5 * width(x).min(width(column))
+}
+
+/// The columns a part covers, or else the one it overlaps the most or is closest to.
+fn part_columns(x: Span, columns: &[Span]) -> (usize, usize) {
+    let mut covered = columns.iter().enumerate()
+        .filter(|&(_, &c)| covers(x, c))
+        .map(|(i, _)| i);
+    match covered.next() {
+        Some(first) => (first, covered.last().unwrap_or(first)),
+        None => {
+            let best = (0 .. columns.len())
+                .max_by(|&a, &b| overlap(x, columns[a]).partial_cmp(&overlap(x, columns[b])).unwrap())
+                .unwrap();
+            (best, best)
+        }
+    }
+}
+
+/// A row of a table, `y` covers the lines that were combined into it.
+#[derive(Debug)]
+struct Row {
+    y: Span,
+    cells: Vec<Cell>,
+}
+
+#[derive(Debug)]
+struct Cell {
+    first_col: usize,
+    last_col: usize,
+    /// The extent of the text of the cell
+    x: Span,
+    y: Span,
+    indices: Vec<usize>,
+    rowspan: usize,
+}
+
+/// Find the cells that span several rows.
+///
+/// A cell takes in the rows above or below it that are empty in its columns and not separated from it
+/// by a rule in its columns, if its text is centered on those rows together rather than on its own row,
+/// or if rules separate those rows in the other columns.
+/// A cell that got a row of its own because it sits between two rows is first moved into the closer one.
+fn rowspans(mut rows: Vec<Row>, columns: &[Span], hsegments: &[(f32, f32, f32)]) -> Vec<Row> {
+    let center = |s: Span| 0.5 * (s.start.into_inner() + s.end.into_inner());
+    // whether a rule runs between the two rows, through the point x if given
+    let ruled = |upper: Span, lower: Span, x: Option<f32>| hsegments.iter().any(|&(y, x1, x2)| {
+        y >= *upper.end - 1.0 && y <= *lower.start + 1.0
+            && x.map_or(true, |x| x1 <= x && x <= x2)
+    });
+    let col_center = |c: &Cell| 0.5 * (*columns[c.first_col].start + *columns[c.last_col].end);
+    let is_free = |row: &Row, c: &Cell| !row.cells.iter().any(|o| o.first_col <= c.last_col && c.first_col <= o.last_col);
+
+    let mut r = 1;
+    while r + 1 < rows.len() {
+        let lone = match rows[r].cells.as_slice() {
+            [cell] => [r - 1, r + 1].iter().all(|&n| {
+                let (upper, lower) = if n < r { (rows[n].y, rows[r].y) } else { (rows[r].y, rows[n].y) };
+                rows[n].cells.len() > 1 && is_free(&rows[n], cell) && !ruled(upper, lower, Some(col_center(cell)))
+            }),
+            _ => false,
+        };
+        if lone {
+            let row = rows.remove(r);
+            let above = *(row.y.start - rows[r - 1].y.end);
+            let below = *(rows[r].y.start - row.y.end);
+            let target = if above <= below { r - 1 } else { r };
+            rows[target].cells.extend(row.cells);
+        } else {
+            r += 1;
+        }
+    }
+
+    let n = rows.len();
+    let mut taken = vec![vec![false; columns.len()]; n];
+    for (r, row) in rows.iter().enumerate() {
+        for c in row.cells.iter() {
+            taken[r][c.first_col ..= c.last_col].iter_mut().for_each(|t| *t = true);
+        }
+    }
+
+    let mut moves = vec![];
+    for r in 0 .. n {
+        for (k, cell) in rows[r].cells.iter().enumerate() {
+            let x = col_center(cell);
+            let m = center(cell.y);
+            let height = (*rows[r].y.end - *rows[r].y.start).max(1.0);
+            let free = |t: usize| !taken[t][cell.first_col ..= cell.last_col].contains(&true);
+            let dist = |(t, b): (usize, usize)| (0.5 * (*rows[t].y.start + *rows[b].y.end) - m).abs();
+
+            let (mut top, mut bottom) = (r, r);
+            loop {
+                let up = top > 0 && free(top - 1) && !ruled(rows[top - 1].y, rows[top].y, Some(x));
+                let down = bottom + 1 < n && free(bottom + 1) && !ruled(rows[bottom].y, rows[bottom + 1].y, Some(x));
+                let mut options = vec![];
+                if up {
+                    options.push((top - 1, bottom));
+                }
+                if down {
+                    options.push((top, bottom + 1));
+                }
+                if up && down {
+                    options.push((top - 1, bottom + 1));
+                }
+                let current = dist((top, bottom));
+                let best = options.into_iter()
+                    .filter(|&(t, b)| {
+                        let centered = dist((t, b)) < current - 0.25 * height;
+                        let ruled_elsewhere = (t == top || ruled(rows[t].y, rows[top].y, None))
+                            && (b == bottom || ruled(rows[bottom].y, rows[b].y, None));
+                        centered || ruled_elsewhere
+                    })
+                    .min_by(|&a, &b| dist(a).partial_cmp(&dist(b)).unwrap());
+                match best {
+                    Some((t, b)) => {
+                        top = t;
+                        bottom = b;
+                    }
+                    None => break,
+                }
+            }
+            if (top, bottom) != (r, r) {
+                moves.push((r, k, top, bottom, cell.first_col, cell.last_col));
+            }
+        }
+        // later cells may not reach into the rows taken by this one
+        for &(_, _, top, bottom, first_col, last_col) in moves.iter().filter(|m| m.0 == r) {
+            for t in top ..= bottom {
+                taken[t][first_col ..= last_col].iter_mut().for_each(|t| *t = true);
+            }
+        }
+    }
+
+    for (r, k, top, bottom, _, _) in moves.into_iter().rev() {
+        let mut cell = rows[r].cells.remove(k);
+        cell.rowspan = bottom - top + 1;
+        rows[top].cells.push(cell);
+    }
+    rows
+}
+
+/// The boundaries of the rows and columns: column `c` runs from `x[c]` to `x[c + 1]`, row `r` from `y[r]` to `y[r + 1]`.
+///
+/// A rule in the space between two columns or rows is their boundary, otherwise the middle of the space.
+/// The outer boundaries are the rules around the table, or else those of the text.
+struct Grid {
+    x: Vec<f32>,
+    y: Vec<f32>,
+}
+impl Grid {
+    fn new(rows: &[Row], columns: &[Span], lines: &Lines) -> Grid {
+        // the text of the cells in only one column may reach further than the column
+        let mut extents: Vec<Span> = columns.to_vec();
+        for cell in rows.iter().flat_map(|r| r.cells.iter()).filter(|c| c.first_col == c.last_col) {
+            extents[cell.first_col] = extents[cell.first_col].hull(cell.x);
+        }
+        let bands: Vec<Span> = rows.iter().map(|r| r.y).collect();
+        let rule_x: Vec<f32> = lines.vlines.iter().map(|&(a, b)| 0.5 * (a + b)).collect();
+        let rule_y: Vec<f32> = lines.hlines.iter().map(|&(a, b)| 0.5 * (a + b)).collect();
+        Grid {
+            x: boundaries(&extents, &rule_x),
+            y: boundaries(&bands, &rule_y),
+        }
+    }
+    fn rect(&self, row: usize, col: usize, rowspan: usize, colspan: usize) -> RectF {
+        RectF::from_points(
+            Vector2F::new(self.x[col], self.y[row]),
+            Vector2F::new(self.x[col + colspan], self.y[row + rowspan]),
+        )
+    }
+}
+
+fn boundaries(parts: &[Span], rules: &[f32]) -> Vec<f32> {
+    // a rule close around the text is the outer boundary
+    let reach = 10.0;
+    let first = parts.iter().map(|s| *s.start).reduce(f32::min).unwrap_or(0.0);
+    let first = rules.iter().cloned().filter(|&r| r <= first && r >= first - reach).reduce(f32::max).unwrap_or(first);
+    let last = parts.iter().map(|s| *s.end).reduce(f32::max).unwrap_or(0.0);
+    let last = rules.iter().cloned().filter(|&r| r >= last && r <= last + reach).reduce(f32::min).unwrap_or(last);
+    let inner = parts.iter().tuple_windows().map(|(a, b)| {
+        let (end, start) = (*a.end, *b.start);
+        rules.iter().cloned()
+            .find(|&r| end.min(start) <= r && r <= end.max(start))
+            .unwrap_or(0.5 * (end + start))
+    });
+    once(first).chain(inner).chain(once(last)).collect()
+}
+
+/// A cell of the header of a table, with the header cells below it within its columns.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HeaderCell<T> {
+    pub value: T,
+    pub children: Vec<HeaderCell<T>>,
+}
+impl<T> HeaderCell<T> {
+    /// Map the values, a cell is left out with its children if `f` returns `None`.
+    pub fn flat_map<U>(&self, f: &mut impl FnMut(&T) -> Option<U>) -> Option<HeaderCell<U>> {
+        Some(HeaderCell {
+            value: f(&self.value)?,
+            children: self.children.iter().filter_map(|c| c.flat_map(f)).collect(),
+        })
+    }
+}
+
+/// The number of rows at the top of the table that form its header.
+///
+/// A rule below the header ends it, unless the data rows are ruled as well, and so do
+/// header rows set in bold above data that is not, and header rows shaded in a colour no data row has.
+/// Without any of these, the header is the rows above the first row with numbers
+/// that have text beyond the first column, unlike section labels.
+fn header_rows<E: Encoder>(rows: &[Row], spans: &[TextSpan<E>], hsegments: &[(f32, f32, f32)], fills: &[(RectF, Fill)]) -> usize {
+    let row_spans = |row: &Row| -> Vec<&TextSpan<E>> {
+        row.cells.iter().flat_map(|c| c.indices.iter()).filter_map(|&i| spans.get(i)).collect()
+    };
+    let numeric = |row: &Row| row.cells.iter()
+        .any(|c| c.indices.len() > 0 && classify(c.indices.iter().filter_map(|&i| spans.get(i))) == Class::Number);
+
+    let first_numeric = rows.iter().position(numeric);
+    let limit = first_numeric.unwrap_or(rows.len()).min(rows.len().saturating_sub(1));
+    if limit == 0 {
+        return 0;
+    }
+
+    let ruled = |r: usize| hsegments.iter().any(|&(y, _, _)| y >= *rows[r].y.end - 1.0 && y <= *rows[r + 1].y.start + 1.0);
+    let data_gaps = rows.len() - 1 - limit;
+    let ruled_data = (limit .. rows.len() - 1).filter(|&r| ruled(r)).count();
+    // rules between all rows, like the edges of zebra stripes, don't tell where the header ends
+    let all_ruled = (0 .. rows.len() - 1).all(ruled);
+    let by_rule = match 2 * ruled_data > data_gaps || all_ruled {
+        true => None,
+        false => (0 .. limit).rev().find(|&r| ruled(r)).map(|r| r + 1),
+    };
+
+    let bold = |row: &Row| classify(row_spans(row).into_iter()) == Class::Header;
+    let by_style = match bold(&rows[limit]) {
+        true => 0,
+        false => rows[..limit].iter().take_while(|row| bold(row)).count(),
+    };
+
+    // the colour of the box behind the whole row
+    let background = |row: &Row| fills.iter()
+        .filter(|(f, _)| f.min_y() <= *row.y.start + 1.0 && *row.y.end <= f.max_y() + 1.0)
+        .filter(|(f, _)| row.cells.iter().all(|c| f.min_x() <= *c.x.start + 1.0 && *c.x.end <= f.max_x() + 1.0))
+        .find_map(|(_, color)| match *color {
+            Fill::Solid(r, g, b) => Some((r, g, b)),
+            _ => None,
+        });
+    let by_fill = match background(&rows[0]) {
+        Some(color) => {
+            let n = rows[..limit].iter().take_while(|row| background(row) == Some(color)).count();
+            match rows[n..].iter().any(|row| background(row) == Some(color)) {
+                true => 0,
+                false => n,
+            }
+        }
+        None => 0,
+    };
+
+    let by_position = match first_numeric {
+        Some(_) => rows[..limit].iter().take_while(|row| row.cells.iter().any(|c| c.last_col > 0)).count(),
+        None => 0,
+    };
+
+    by_rule
+        .or((by_style > 0).then(|| by_style))
+        .or((by_fill > 0).then(|| by_fill))
+        .unwrap_or(by_position)
+}
+
+/// The cells of the header rows as a tree, the roots are those with no header cell above them.
+fn header_tree(rows: &[Row], grid: &Grid) -> Vec<HeaderCell<TableCell>> {
+    let mut cells: Vec<(usize, &Cell)> = rows.iter().enumerate()
+        .flat_map(|(r, row)| row.cells.iter().map(move |c| (r, c)))
+        .collect();
+    cells.sort_by_key(|&(r, c)| (r, c.first_col));
+
+    // the cells from row `below` on within the columns, that no other of them is above
+    fn children(cells: &[(usize, &Cell)], grid: &Grid, below: usize, first_col: usize, last_col: usize) -> Vec<HeaderCell<TableCell>> {
+        let inside: Vec<(usize, &Cell)> = cells.iter().cloned()
+            .filter(|&(r, c)| r >= below && c.first_col >= first_col && c.last_col <= last_col)
+            .collect();
+        inside.iter()
+            .filter(|&&(r, c)| !inside.iter().any(|&(r2, c2)| r2 < r && c2.first_col <= c.first_col && c.last_col <= c2.last_col))
+            .map(|&(r, c)| HeaderCell {
+                value: TableCell::new(r, c, grid),
+                children: children(cells, grid, r + c.rowspan, c.first_col, c.last_col),
+            })
+            .collect()
+    }
+    children(&cells, grid, 0, 0, usize::MAX)
+}
+
 #[derive(Debug)]
 enum LineTag {
     Unknown,
@@ -154,7 +730,11 @@ fn build_line<E: Encoder>(boxes: &[(RectF, usize)], spans: &[TextSpan<E>]) -> (L
     }
     line.push((x, items));
 
-    let avg_font_size = avg(boxes.iter().filter_map(|&(_, i)| spans.get(i)).map(|s| s.font_size)).unwrap();
+    // a line of figures alone is no row of a table
+    let avg_font_size = match avg(boxes.iter().filter_map(|&(_, i)| spans.get(i)).map(|s| s.font_size)) {
+        Some(size) => size,
+        None => return (LineTag::Text, y, line),
+    };
 
     let max_gap = line.iter().tuple_windows().map(|(l, r)| r.0.start - l.0.end).max();
     let tag = match max_gap {
@@ -199,6 +779,14 @@ impl Span {
         }
     }
 
+    /// The span from the start of the first to the end of the second, even if they do not overlap.
+    fn hull(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
     fn union(self, other: Span) -> Option<Span> {
         if self.start <= other.end && other.start <= self.end {
             Some(Span {
@@ -250,4 +838,450 @@ fn split_v(boxes: &mut [(RectF, usize)]) -> Node {
         cells,
         tag,
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::vector::Vector2F;
+    use pdf_render::font::OutlineBuilder;
+    use crate::flow::{Run, RunType};
+    use crate::testing::{analyze, content, rounded_rect, span, table_node, table_spans};
+
+    #[test]
+    fn test_number_columns() {
+        // two columns of numbers right aligned at 200 and 300, with the parentheses of negatives sticking out
+        let spans = vec![
+            span("Item", 0.0, 10.0, 10.0), span("Amount in USD", 140.0, 10.0, 10.0), span("Prior", 275.0, 10.0, 10.0),
+            span("Revenue", 0.0, 30.0, 10.0), span("1,200", 175.0, 30.0, 10.0), span("2,000", 275.0, 30.0, 10.0),
+            span("Costs", 0.0, 50.0, 10.0), span("(300)", 180.0, 50.0, 10.0), span("(45)", 285.0, 50.0, 10.0),
+            span("Other", 0.0, 70.0, 10.0), span("15", 190.0, 70.0, 10.0), span("7", 295.0, 70.0, 10.0),
+        ];
+        let lines: Vec<_> = spans.chunks(3).enumerate()
+            .map(|(l, _)| {
+                let boxes: Vec<(RectF, usize)> = (3 * l .. 3 * l + 3).map(|i| (spans[i].rect, i)).collect();
+                build_line(&boxes, &spans)
+            })
+            .collect();
+
+        let (columns, numbers) = build_columns(&lines, &spans);
+        assert_eq!(columns.len(), 3);
+        for l in 1..4 {
+            assert_eq!(numbers.get(&(l, 1)), Some(&1));
+            assert_eq!(numbers.get(&(l, 2)), Some(&2));
+        }
+        // the header wider than its numbers stays in their column
+        assert_eq!(part_columns(lines[0].2[1].0, &columns), (1, 1));
+        assert_eq!(part_columns(lines[0].2[2].0, &columns), (2, 2));
+    }
+
+    #[test]
+    fn test_decimal_comma_columns() {
+        // amounts with a decimal comma, lined up on it under a wider header, the negatives in parentheses sticking out
+        let spans = vec![
+            span("Item", 150.0, 100.0, 10.0), span("Amount in EUR", 270.0, 100.0, 10.0), span("Prior", 410.0, 100.0, 10.0),
+            span("Revenue", 150.0, 130.0, 10.0), span("1.200,50", 295.0, 130.0, 10.0), span("2.000,00", 415.0, 130.0, 10.0),
+            span("Costs", 150.0, 160.0, 10.0), span("(300,25)", 300.0, 160.0, 10.0), span("(45,10)", 425.0, 160.0, 10.0),
+            span("Other", 150.0, 190.0, 10.0), span("15,00", 310.0, 190.0, 10.0), span("7,50", 435.0, 190.0, 10.0),
+        ];
+        let mut boxes: Vec<(RectF, usize)> = spans.iter().enumerate().map(|(i, s)| (s.rect, i)).collect();
+        let table = match split(&mut boxes, &spans, &crate::node::line::analyze_lines(&[])) {
+            Node::Table { table, .. } => table,
+            node => panic!("no table: {:?}", node),
+        };
+        let col = |i: usize| table.values().map(|v| &v.value).find(|c| c.indices == [i]).map(|c| c.col);
+        assert_eq!(table.values().map(|v| v.value.col + v.value.colspan).max(), Some(3));
+        assert_eq!(col(1), Some(1));
+        assert_eq!(col(7), Some(1));
+        assert_eq!(col(11), Some(2));
+    }
+
+    #[test]
+    fn test_figure_line() {
+        // a line of nothing but a figure, its index is past the spans
+        let spans = vec![span("Revenue", 150.0, 130.0, 10.0)];
+        let figure = RectF::from_points(Vector2F::new(150.0, 172.0), Vector2F::new(210.0, 202.0));
+        let (tag, _, parts) = build_line(&[(figure, 1)], &spans);
+        assert!(matches!(tag, LineTag::Text));
+        assert_eq!(parts.len(), 1);
+    }
+
+    fn row(y: (f32, f32), cells: &[(usize, (f32, f32))]) -> Row {
+        let y = Span::new(y.0, y.1).unwrap();
+        Row {
+            y,
+            cells: cells.iter().map(|&(col, (a, b))| Cell {
+                first_col: col,
+                last_col: col,
+                x: Span::new(50.0 * col as f32, 50.0 * col as f32 + 30.0).unwrap(),
+                y: Span::new(a, b).unwrap(),
+                indices: vec![],
+                rowspan: 1,
+            }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_rowspans() {
+        let columns = [Span::new(0.0, 30.0).unwrap(), Span::new(50.0, 80.0).unwrap(), Span::new(100.0, 130.0).unwrap()];
+
+        // a label centered between two rows, without any rules
+        let rows = vec![
+            row((0.0, 10.0), &[(1, (0.0, 10.0)), (2, (0.0, 10.0))]),
+            row((12.0, 22.0), &[(0, (12.0, 22.0))]),
+            row((24.0, 34.0), &[(1, (24.0, 34.0)), (2, (24.0, 34.0))]),
+        ];
+        let rows = rowspans(rows, &columns, &[]);
+        assert_eq!(rows.len(), 2);
+        let label = rows[0].cells.iter().find(|c| c.first_col == 0).unwrap();
+        assert_eq!(label.rowspan, 2);
+
+        // a top aligned label in a ruled table, with no rule below it in its column
+        let rows = vec![
+            row((0.0, 10.0), &[(0, (0.0, 10.0)), (1, (0.0, 10.0))]),
+            row((14.0, 24.0), &[(1, (14.0, 24.0))]),
+            row((28.0, 38.0), &[(0, (28.0, 38.0)), (1, (28.0, 38.0))]),
+        ];
+        let rules = [(12.0, 45.0, 135.0), (26.0, 0.0, 135.0)];
+        let rows = rowspans(rows, &columns, &rules);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].cells.iter().find(|c| c.first_col == 0).unwrap().rowspan, 2);
+        assert_eq!(rows[2].cells.iter().find(|c| c.first_col == 0).unwrap().rowspan, 1);
+
+        // without rules, empty cells stay empty
+        let rows = vec![
+            row((0.0, 10.0), &[(0, (0.0, 10.0)), (1, (0.0, 10.0))]),
+            row((14.0, 24.0), &[(1, (14.0, 24.0))]),
+        ];
+        let rows = rowspans(rows, &columns, &[]);
+        assert!(rows.iter().flat_map(|r| r.cells.iter()).all(|c| c.rowspan == 1));
+    }
+
+    /// The spans and rows of a table with a line of text in each cell.
+    fn grid(texts: &[[&str; 3]]) -> (Vec<TextSpan<OutlineBuilder>>, Vec<Row>) {
+        let mut spans = vec![];
+        let mut rows = vec![];
+        for (r, texts) in texts.iter().enumerate() {
+            let y = Span::new(100.0 + 14.0 * r as f32, 110.0 + 14.0 * r as f32).unwrap();
+            let mut cells = vec![];
+            for (c, text) in texts.iter().enumerate() {
+                let span = span(text, 50.0 + 60.0 * c as f32, *y.end, 10.0);
+                let x = Span::new(span.rect.min_x(), span.rect.max_x()).unwrap();
+                cells.push(Cell { first_col: c, last_col: c, x, y, indices: vec![spans.len()], rowspan: 1 });
+                spans.push(span);
+            }
+            rows.push(Row { y, cells });
+        }
+        (spans, rows)
+    }
+
+    #[test]
+    fn test_header_rows() {
+        // the rows above the first one with numbers
+        let (spans, rows) = grid(&[["Fruit", "Price", "Qty"], ["Apples", "1.20", "4"], ["Pears", "0.80", "12"]]);
+        assert_eq!(header_rows(&rows, &spans, &[], &[]), 1);
+
+        // a rule below the header ends it before a row of text
+        let (spans, rows) = grid(&[["Fruit", "Price", "Qty"], ["Fresh", "yes", "no"], ["Apples", "1.20", "4"], ["Pears", "0.80", "12"]]);
+        assert_eq!(header_rows(&rows, &spans, &[], &[]), 2);
+        assert_eq!(header_rows(&rows, &spans, &[(112.0, 50.0, 230.0)], &[]), 1);
+        // unless all rows are ruled
+        let rules: Vec<_> = (0..3).map(|r| (112.0 + 14.0 * r as f32, 50.0, 230.0)).collect();
+        assert_eq!(header_rows(&rows, &spans, &rules, &[]), 2);
+
+        // no header without numbers below it
+        let (spans, rows) = grid(&[["Fruit", "Color", "Taste"], ["Apples", "red", "sweet"], ["Lemons", "yellow", "sour"]]);
+        assert_eq!(header_rows(&rows, &spans, &[], &[]), 0);
+    }
+
+    #[test]
+    fn test_header_fill() {
+        // zebra stripes behind every other row, all of them ruled by the edges of the stripes
+        let (spans, rows) = grid(&[["Fruit", "Color", "Taste"], ["Apples", "red", "sweet"], ["Lemons", "yellow", "sour"], ["Plums", "blue", "sour"], ["Pears", "green", "sweet"]]);
+        let shade = |r: usize, color: Fill| (RectF::from_points(Vector2F::new(45.0, 99.0 + 14.0 * r as f32), Vector2F::new(240.0, 113.0 + 14.0 * r as f32)), color);
+        let grey = || Fill::Solid(0.9, 0.9, 0.9);
+        let rules: Vec<_> = (0..4).map(|r| (112.0 + 14.0 * r as f32, 45.0, 240.0)).collect();
+
+        // a header row in a dark colour of its own
+        let fills = [shade(0, Fill::Solid(0.2, 0.3, 0.5)), shade(2, grey()), shade(4, grey())];
+        assert_eq!(header_rows(&rows, &spans, &rules, &fills), 1);
+        // the first row in the colour of the stripes is data
+        let fills = [shade(0, grey()), shade(2, grey()), shade(4, grey())];
+        assert_eq!(header_rows(&rows, &spans, &rules, &fills), 0);
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let parts: Vec<Span> = [(0.0, 30.0), (50.0, 80.0), (100.0, 130.0)].iter().map(|&(a, b)| Span::new(a, b).unwrap()).collect();
+        // a rule between the first two columns, the middle of the space between the others
+        assert_eq!(boundaries(&parts, &[45.0, 200.0]), [0.0, 45.0, 90.0, 130.0]);
+        // rules close around the text are the outer boundaries
+        assert_eq!(boundaries(&parts, &[-5.0, 45.0, 140.0]), [-5.0, 45.0, 90.0, 140.0]);
+    }
+
+    #[test]
+    fn test_header_tree() {
+        // | Name | Size       |
+        // |      | min | max |
+        let mut top = row((0.0, 10.0), &[(0, (0.0, 24.0)), (1, (0.0, 10.0))]);
+        top.cells[0].rowspan = 2;
+        top.cells[1].last_col = 2;
+        let rows = vec![top, row((14.0, 24.0), &[(1, (14.0, 24.0)), (2, (14.0, 24.0))])];
+
+        let grid = Grid { x: vec![0.0, 40.0, 90.0, 130.0], y: vec![0.0, 12.0, 24.0] };
+        let tree = header_tree(&rows, &grid);
+        assert_eq!(tree.len(), 2);
+        assert_eq!((tree[0].value.col, tree[0].value.rowspan, tree[0].children.len()), (0, 2, 0));
+        assert_eq!((tree[1].value.col, tree[1].value.colspan), (1, 2));
+        assert_eq!(tree[1].children.iter().map(|c| (c.value.row, c.value.col)).collect::<Vec<_>>(), [(1, 1), (1, 2)]);
+        assert_eq!(tree[0].value.rect, RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(40.0, 24.0)));
+    }
+
+    #[test]
+    fn test_nested_table() {
+        use crate::node::line::analyze_lines;
+
+        // a ruled table of two columns, the "Sizes" row holds a 2x2 grid in its second cell
+        let mut lines = vec![];
+        for y in [80.0, 120.0, 200.0, 240.0, 280.0] {
+            lines.push([100.0, y, 500.0, y]);
+        }
+        for x in [100.0, 250.0, 500.0] {
+            lines.push([x, 80.0, x, 280.0]);
+        }
+        for y in [125.0, 160.0, 195.0] {
+            lines.push([255.0, y, 495.0, y]);
+        }
+        for x in [255.0, 370.0, 495.0] {
+            lines.push([x, 125.0, x, 195.0]);
+        }
+        let spans = vec![
+            span("Item", 110.0, 105.0, 10.0), span("Details", 280.0, 105.0, 10.0),
+            span("Sizes", 110.0, 150.0, 10.0), span("S", 280.0, 150.0, 10.0), span("M", 380.0, 150.0, 10.0),
+            span("10", 280.0, 180.0, 10.0), span("12", 380.0, 180.0, 10.0),
+            span("Weight", 110.0, 225.0, 10.0), span("5 kg", 280.0, 225.0, 10.0),
+            span("Colour", 110.0, 265.0, 10.0), span("Red", 280.0, 265.0, 10.0),
+        ];
+        let mut boxes: Vec<(RectF, usize)> = spans.iter().enumerate().map(|(i, s)| (s.rect, i)).collect();
+        let table = match split(&mut boxes, &spans, &analyze_lines(&lines)) {
+            Node::Table { table, .. } => table,
+            node => panic!("no table: {:?}", node),
+        };
+
+        // the rules and text of the inner grid don't add rows or columns to the outer one
+        let cells: Vec<&TableCell> = table.values().map(|v| &v.value).collect();
+        assert_eq!(cells.iter().map(|c| c.row + c.rowspan).max(), Some(4));
+        assert_eq!(cells.iter().map(|c| c.col + c.colspan).max(), Some(2));
+        let sizes = cells.iter().find(|c| c.row == 1 && c.col == 1).unwrap();
+        assert_eq!(sizes.rect, RectF::from_points(Vector2F::new(250.0, 120.0), Vector2F::new(500.0, 200.0)));
+        let nested = match sizes.nested.as_deref() {
+            Some(Node::Table { table, .. }) => table,
+            node => panic!("no nested table: {:?}", node),
+        };
+        let cell = |row, col| nested.values().map(|v| &v.value).find(|c| c.row == row && c.col == col).map(|c| c.indices.clone());
+        assert_eq!(cell(0, 1), Some(vec![4]));
+        assert_eq!(cell(1, 0), Some(vec![5]));
+    }
+
+    #[test]
+    fn test_page_table() {
+        let a = analyze(content(table_spans(&[["Fruit", "Price", "Qty"], ["Apples", "1.20", "4"], ["Pears", "0.80", "12"], ["Plums", "2.50", "7"]], 100.0), &[]));
+
+        assert!(matches!(table_node(&a.root), Some(Node::Table { .. })));
+        assert_eq!(a.flow.tables.len(), 1);
+        let table = &a.flow.tables[0];
+        assert_eq!((table.rows, table.columns), (4, 3));
+        let cell = |row, col| table.table.values().map(|v| &v.value).find(|c| c.row == row && c.col == col).map(|c| c.text.as_str());
+        assert_eq!(cell(2, 0), Some("Pears"));
+        assert_eq!(cell(3, 2), Some("7"));
+
+        // the cells are runs of the flow in reading order
+        let cells: Vec<(usize, usize)> = a.flow.runs.iter()
+            .filter_map(|r| match r.kind {
+                RunType::Cell { table: 0, row, col } => Some((row, col)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(cells.len(), 12);
+        assert_eq!(cells[..4], [(0, 0), (0, 1), (0, 2), (1, 0)]);
+
+        // wide enough for the gaps between the columns to look like gutters between columns of text
+        let a = analyze(content(table_spans(&[["Fruit", "Price", "Qty"], ["Green apples", "1.20", "4"], ["Pears from Italy", "0.80", "12"], ["Plums", "2.50", "7"]], 100.0), &[]));
+        assert_eq!(a.flow.tables.len(), 1);
+
+        // lines of text are no table
+        let spans = vec![
+            span("The first line of a paragraph", 150.0, 100.0, 10.0),
+            span("and the second line of it,", 150.0, 115.0, 10.0),
+            span("then the last one.", 150.0, 130.0, 10.0),
+        ];
+        let a = analyze(content(spans, &[]));
+        assert!(table_node(&a.root).is_none());
+        assert!(a.flow.tables.is_empty());
+    }
+
+    #[test]
+    fn test_no_table() {
+        // a table of contents, the page numbers are joined to the titles by dot leaders
+        let mut spans = vec![];
+        for (l, (title, page)) in [("Introduction", "1"), ("Methods", "5"), ("Results", "12"), ("Discussion", "27"), ("References", "31")].iter().enumerate() {
+            let baseline = 100.0 + 15.0 * l as f32;
+            let end = 150.0 + 5.0 * title.len() as f32;
+            spans.push(span(title, 150.0, baseline, 10.0));
+            spans.push(span(&".".repeat(((440.0 - end) / 5.0) as usize - 1), end + 3.0, baseline, 10.0));
+            spans.push(span(page, 450.0 - 5.0 * page.len() as f32, baseline, 10.0));
+        }
+        let a = analyze(content(spans, &[]));
+        assert!(table_node(&a.root).is_none());
+        assert!(a.flow.tables.is_empty());
+
+        // a body of two columns of text, with a rule in the gutter and one below the heading
+        let mut spans = vec![span("A heading across both columns", 100.0, 80.0, 10.0)];
+        for l in 0 .. 20 {
+            let baseline = 120.0 + 12.0 * l as f32;
+            let (left, right) = match l % 7 {
+                6 => ("the end.", "the end."),
+                _ => ("a line of the text as wide as its column", "a line of the text as wide as its column"),
+            };
+            spans.push(span(left, 100.0, baseline, 10.0));
+            spans.push(span(right, 320.0, baseline, 10.0));
+        }
+        let rules = [[100.0, 95.0, 520.0, 95.0], [310.0, 105.0, 310.0, 365.0]];
+        let a = analyze(content(spans, &rules));
+        assert!(table_node(&a.root).is_none());
+        assert!(a.flow.tables.is_empty());
+    }
+
+    #[test]
+    fn test_page_header() {
+        // the rows above the first one with numbers
+        let a = analyze(content(table_spans(&[["Fruit", "Price", "Qty"], ["Apples", "1.20", "4"], ["Pears", "0.80", "12"], ["Plums", "2.50", "7"]], 100.0), &[]));
+        let table = &a.flow.tables[0];
+        assert_eq!(table.header_rows, 1);
+        let header: Vec<&str> = table.header.iter().map(|c| c.value.text.as_str()).collect();
+        assert_eq!(header, ["Fruit", "Price", "Qty"]);
+
+        // a rule below the header ends it before a row of text
+        let spans = table_spans(&[["Fruit", "Price", "Qty"], ["Fresh", "yes", "no"], ["Apples", "1.20", "4"], ["Pears", "0.80", "12"], ["Plums", "2.50", "7"]], 100.0);
+        let a = analyze(content(spans, &[[140.0, 110.0, 440.0, 110.0]]));
+        assert_eq!(a.flow.tables[0].header_rows, 1);
+    }
+
+    #[test]
+    fn test_zebra_table() {
+        // a table of text alone, without rules: the shaded rows tell it from columns of text
+        let rows = [["Name", "Role", "City"], ["Alice", "Editor", "Paris"], ["Bob", "Author", "Lyon"], ["Carol", "Printer", "Nice"], ["Dave", "Binder", "Metz"]];
+        let a = analyze(content(table_spans(&rows, 100.0), &[]));
+        assert!(a.flow.tables.is_empty());
+
+        // one stripe is a plain rectangle, the other one has rounded corners
+        let stripe = |r: usize| RectF::new(Vector2F::new(140.0, 80.0 + 30.0 * r as f32), Vector2F::new(340.0, 30.0));
+        let mut page = content(table_spans(&rows, 100.0), &[]);
+        page.push_path(Outline::from_rect(stripe(1)), Some(Fill::Solid(0.9, 0.9, 0.9)));
+        page.push_path(rounded_rect(stripe(3), 4.0), Some(Fill::Solid(0.9, 0.9, 0.9)));
+        assert_eq!(page.fills.len(), 2);
+        let a = analyze(page);
+        assert_eq!(a.flow.tables.len(), 1);
+        assert_eq!((a.flow.tables[0].rows, a.flow.tables[0].columns), (5, 3));
+        assert_eq!(a.flow.tables[0].header_rows, 0);
+        // the edges the rounded corners cut short are used for the layout, they are not drawn
+        assert!(!a.lines.contains(&[140.0, 170.0, 480.0, 170.0]));
+
+        // stroked, the boxes are rules around the rows, one below the header
+        let mut page = content(table_spans(&rows, 100.0), &[]);
+        page.push_path(Outline::from_rect(stripe(0)), None);
+        assert!(page.fills.is_empty());
+        let a = analyze(page);
+        assert_eq!(a.flow.tables.len(), 1);
+        assert_eq!(a.flow.tables[0].header_rows, 1);
+    }
+
+    #[test]
+    fn test_page_cells() {
+        // a table in small print at the bottom of the page, its numbered rows are no footnotes
+        let mut spans: Vec<_> = (0..12).map(|l| span("The body of the page runs over a dozen lines", 150.0, 100.0 + 15.0 * l as f32, 10.0)).collect();
+        for (r, row) in [["1.", "Apples", "4.20"], ["2.", "Pears", "3.10"], ["3.", "Plums", "2.50"]].iter().enumerate() {
+            let baseline = 650.0 + 20.0 * r as f32;
+            spans.extend([span(row[0], 150.0, baseline, 7.0), span(row[1], 250.0, baseline, 7.0), span(row[2], 400.0, baseline, 7.0)]);
+        }
+        let a = analyze(content(spans, &[]));
+        assert_eq!(a.flow.tables.len(), 1);
+        assert_eq!(a.flow.runs.iter().filter(|r| matches!(r.kind, RunType::Cell { .. })).count(), 9);
+        assert!(!a.flow.runs.iter().any(|r| matches!(r.kind, RunType::Footnote { .. })));
+
+        // the caption above a table refers to it, its cells are no captions
+        let mut spans = table_spans(&[["Table", "Price", "Qty"], ["Apples", "1.20", "4"], ["Pears", "0.80", "12"], ["Plums", "2.50", "7"]], 100.0);
+        spans.push(span("Table 1: Prices of fruit", 150.0, 70.0, 10.0));
+        let a = analyze(content(spans, &[]));
+        assert_eq!(a.flow.tables.len(), 1);
+        assert_eq!(a.flow.runs.iter().filter(|r| matches!(r.kind, RunType::Cell { .. })).count(), 12);
+        let captions: Vec<&Run> = a.flow.runs.iter().filter(|r| matches!(r.kind, RunType::Caption { .. })).collect();
+        assert_eq!(captions.len(), 1);
+        assert_eq!(captions[0].kind, RunType::Caption { target: Some(a.flow.tables[0].rect) });
+    }
+
+    #[test]
+    fn test_page_number_columns() {
+        // amounts with a decimal comma, lined up on it under a wider header, the negatives in parentheses sticking out
+        let spans = |last: f32| vec![
+            span("Item", 150.0, 100.0, 10.0), span("Amount in EUR", 270.0, 100.0, 10.0), span("Prior", 410.0, 100.0, 10.0),
+            span("Revenue", 150.0, 130.0, 10.0), span("1.200,50", 295.0, 130.0, 10.0), span("2.000,00", 415.0, 130.0, 10.0),
+            span("Costs", 150.0, 160.0, 10.0), span("(300,25)", 300.0, 160.0, 10.0), span("(45,10)", 425.0, 160.0, 10.0),
+            span("Other", 150.0, last, 10.0), span("15,00", 310.0, last, 10.0), span("7,50", 435.0, last, 10.0),
+        ];
+        let a = analyze(content(spans(190.0), &[]));
+        assert_eq!(a.flow.tables.len(), 1);
+        let table = &a.flow.tables[0];
+        assert_eq!((table.rows, table.columns), (4, 3));
+        let cell = |text: &str| table.table.values().map(|v| &v.value).find(|c| c.text == text).map(|c| (c.col, c.value.clone()));
+        assert_eq!(cell("Amount in EUR"), Some((1, None)));
+        assert_eq!(cell("(300,25)"), Some((1, Some(Value::Number(-300.25)))));
+        assert_eq!(cell("7,50"), Some((2, Some(Value::Number(7.5)))));
+
+        // a picture alone on a line between the rows is no part of the table
+        let mut page = content(spans(220.0), &[]);
+        page.images.push((RectF::new(Vector2F::new(150.0, 172.0), Vector2F::new(60.0, 30.0)), None));
+        let a = analyze(page);
+        assert_eq!(a.flow.runs.iter().filter(|r| matches!(r.kind, RunType::Figure { .. })).count(), 1);
+        assert_eq!(a.flow.tables.iter().map(|t| t.rows).collect::<Vec<_>>(), [3, 1]);
+    }
+
+    #[test]
+    fn test_page_nested_table() {
+        // a ruled table of two columns, the "Sizes" row holds a 2x2 grid in its second cell
+        let mut lines = vec![];
+        for y in [80.0, 120.0, 200.0, 240.0, 280.0] {
+            lines.push([100.0, y, 500.0, y]);
+        }
+        for x in [100.0, 250.0, 500.0] {
+            lines.push([x, 80.0, x, 280.0]);
+        }
+        for y in [125.0, 160.0, 195.0] {
+            lines.push([255.0, y, 495.0, y]);
+        }
+        for x in [255.0, 370.0, 495.0] {
+            lines.push([x, 125.0, x, 195.0]);
+        }
+        let spans = vec![
+            span("Item", 110.0, 105.0, 10.0), span("Details", 280.0, 105.0, 10.0),
+            span("Sizes", 110.0, 150.0, 10.0), span("S", 280.0, 150.0, 10.0), span("M", 380.0, 150.0, 10.0),
+            span("10", 280.0, 180.0, 10.0), span("12", 380.0, 180.0, 10.0),
+            span("Weight", 110.0, 225.0, 10.0), span("5 kg", 280.0, 225.0, 10.0),
+            span("Colour", 110.0, 265.0, 10.0), span("Red", 280.0, 265.0, 10.0),
+        ];
+        let a = analyze(content(spans, &lines));
+
+        // the rules and text of the inner grid don't add rows or columns to the outer one
+        assert_eq!(a.flow.tables.len(), 1);
+        let table = &a.flow.tables[0];
+        assert_eq!((table.rows, table.columns), (4, 2));
+        let sizes = table.table.values().map(|v| &v.value).find(|c| c.row == 1 && c.col == 1).unwrap();
+        assert_eq!((sizes.grid_rect.x, sizes.grid_rect.y, sizes.grid_rect.w, sizes.grid_rect.h), (250.0, 120.0, 250.0, 80.0));
+        let nested = sizes.nested.as_ref().unwrap();
+        assert_eq!((nested.rows, nested.columns), (2, 2));
+        let cell = |row, col| nested.table.values().map(|v| &v.value).find(|c| c.row == row && c.col == col).map(|c| c.text.as_str());
+        assert_eq!(cell(0, 1), Some("M"));
+        assert_eq!(cell(1, 0), Some("10"));
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::BinaryHeap;
+use std::cmp::Ordering;
+
+use font::Encoder;
+use pathfinder_geometry::{rect::RectF, vector::Vector2F};
+use pdf_render::TextSpan;
+
+use crate::util::{median, root};
+
+use super::{column::reading_order, line::Lines, split, Node, NodeTag};
+
+/// Give up the search for separators after this many steps.
+const MAX_STEPS: usize = 20_000;
+/// Find at most this many separators.
+const MAX_SEPARATORS: usize = 32;
+
+/// A candidate rectangle of the search, with the obstacles inside it.
+struct Candidate {
+    quality: f32,
+    bound: RectF,
+    obstacles: Vec<RectF>,
+}
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.quality == other.quality
+    }
+}
+impl Eq for Candidate {}
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.quality.partial_cmp(&other.quality).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Whether the interiors of the rectangles overlap.
+fn overlaps(a: &RectF, b: &RectF) -> bool {
+    a.min_x() < b.max_x() && b.min_x() < a.max_x() && a.min_y() < b.max_y() && b.min_y() < a.max_y()
+}
+
+/// Find the column separators: tall empty rectangles between the boxes, with text on either side.
+///
+/// This is the branch and bound search for maximal empty rectangles of Breuel.
+/// A candidate is split around the obstacle closest to its center into the four rectangles
+/// left, right, above and below of it, the best candidate is looked at first.
+/// Once a candidate has no obstacles left, it is a maximal empty rectangle.
+/// If it borders on text on both sides, it becomes a separator and an obstacle for the rest of the search.
+pub fn separators(boxes: &[(RectF, usize)], content: RectF, line_height: f32) -> Vec<RectF> {
+    let min_width = 0.8 * line_height;
+    let min_height = 3.0 * line_height;
+    // tall rectangles are better separators, very wide ones are not
+    let quality = |r: &RectF| r.height() * r.width().min(4.0 * line_height);
+    let possible = |r: &RectF| r.width() >= min_width && r.height() >= min_height;
+
+    let mut found: Vec<RectF> = vec![];
+    let mut queue = BinaryHeap::new();
+    queue.push(Candidate {
+        quality: quality(&content),
+        bound: content,
+        obstacles: boxes.iter().map(|&(r, _)| r).collect(),
+    });
+
+    let mut steps = 0;
+    while let Some(Candidate { bound, mut obstacles, .. }) = queue.pop() {
+        steps += 1;
+        if steps > MAX_STEPS || found.len() >= MAX_SEPARATORS {
+            break;
+        }
+        // separators found since this candidate was queued
+        obstacles.extend(found.iter().filter(|f| overlaps(f, &bound)));
+
+        let center = bound.center();
+        let pivot = obstacles.iter()
+            .min_by(|a, b| {
+                let d = |r: &RectF| (r.center() - center).square_length();
+                d(a).total_cmp(&d(b))
+            })
+            .cloned();
+
+        let pivot = match pivot {
+            Some(pivot) => pivot,
+            None => {
+                if borders_on_text(boxes, bound, line_height) {
+                    found.push(bound);
+                }
+                continue;
+            }
+        };
+
+        let parts = [
+            RectF::from_points(bound.origin(), Vector2F::new(pivot.min_x(), bound.max_y())),
+            RectF::from_points(Vector2F::new(pivot.max_x(), bound.min_y()), bound.lower_right()),
+            RectF::from_points(bound.origin(), Vector2F::new(bound.max_x(), pivot.min_y())),
+            RectF::from_points(Vector2F::new(bound.min_x(), pivot.max_y()), bound.lower_right()),
+        ];
+        for part in parts {
+            if !possible(&part) {
+                continue;
+            }
+            queue.push(Candidate {
+                quality: quality(&part),
+                bound: part,
+                obstacles: obstacles.iter().filter(|o| overlaps(o, &part)).cloned().collect(),
+            });
+        }
+    }
+    found
+}
+
+/// Whether at least two boxes end just left of the rectangle and two start just right of it.
+fn borders_on_text(boxes: &[(RectF, usize)], r: RectF, line_height: f32) -> bool {
+    let beside = |b: &RectF| b.min_y() < r.max_y() && r.min_y() < b.max_y();
+    let left = boxes.iter()
+        .filter(|(b, _)| beside(b) && b.max_x() <= r.min_x() + 0.1 && b.max_x() > r.min_x() - line_height)
+        .count();
+    let right = boxes.iter()
+        .filter(|(b, _)| beside(b) && b.min_x() >= r.max_x() - 0.1 && b.min_x() < r.max_x() + line_height)
+        .count();
+    left >= 2 && right >= 2
+}
+
+/// Split the page with the whitespace cover: the separators and the long vertical rules divide the page
+/// into blocks of text, which are split further with the XY cut and put in reading order.
+///
+/// Unlike the XY cut, this does not need a gap running through the whole page,
+/// so it handles L-shaped layouts and text flowing around a figure.
+pub fn split_blocks<E: Encoder>(boxes: &mut [(RectF, usize)], spans: &[TextSpan<E>], lines: &Lines) -> Node {
+    if boxes.len() < 2 {
+        return Node::singleton(boxes);
+    }
+    let line_height = median(boxes.iter().map(|(r, _)| r.height()).collect()).unwrap().max(1.0);
+    let content = boxes.iter().map(|&(r, _)| r).reduce(|a, b| a.union_rect(b)).unwrap();
+
+    let mut separators = separators(boxes, content, line_height);
+    separators.extend(lines.vlines.iter()
+        .map(|&(a, b)| 0.5 * (a + b))
+        .filter(|&x| x > content.min_x() && x < content.max_x())
+        .map(|x| RectF::from_points(Vector2F::new(x - 0.5, content.min_y()), Vector2F::new(x + 0.5, content.max_y())))
+    );
+
+    // join boxes that are close to each other and not separated, with union-find
+    let n = boxes.len();
+    let mut parent: Vec<usize> = (0..n).collect();
+    for i in 0..n {
+        for j in i + 1..n {
+            let (a, b) = (boxes[i].0, boxes[j].0);
+            let same_line = a.min_y() < b.max_y() && b.min_y() < a.max_y();
+            let stacked = a.min_x() < b.max_x() && b.min_x() < a.max_x();
+            let dx = (a.min_x() - b.max_x()).max(b.min_x() - a.max_x());
+            let dy = (a.min_y() - b.max_y()).max(b.min_y() - a.max_y());
+            let close = (same_line && dx < 3.0 * line_height) || (stacked && dy < 1.2 * line_height);
+            if !close {
+                continue;
+            }
+            let hull = a.union_rect(b);
+            if separators.iter().any(|s| overlaps(s, &hull)) {
+                continue;
+            }
+            let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+            parent[ri] = rj;
+        }
+    }
+
+    let mut groups: Vec<Vec<(RectF, usize)>> = vec![];
+    let mut group_of = vec![usize::MAX; n];
+    for i in 0..n {
+        let r = root(&mut parent, i);
+        if group_of[r] == usize::MAX {
+            group_of[r] = groups.len();
+            groups.push(vec![]);
+        }
+        groups[group_of[r]].push(boxes[i]);
+    }
+
+    if groups.len() == 1 {
+        return split(boxes, spans, lines);
+    }
+
+    let rects: Vec<RectF> = groups.iter()
+        .map(|g| g.iter().map(|&(r, _)| r).reduce(|a, b| a.union_rect(b)).unwrap())
+        .collect();
+    let mut blocks: Vec<Option<Node>> = groups.into_iter()
+        .map(|mut g| Some(split(&mut g, spans, lines)))
+        .collect();
+    let cells = reading_order(&rects).into_iter()
+        .filter_map(|i| blocks[i].take())
+        .collect();
+
+    Node::Grid {
+        x: vec![],
+        y: vec![],
+        cells,
+        tag: NodeTag::Complex,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::line::analyze_lines;
+    use pdf_render::font::OutlineBuilder;
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> RectF {
+        RectF::from_points(Vector2F::new(x0, y0), Vector2F::new(x1, y1))
+    }
+
+    /// A full width paragraph on top, below it a figure caption on the left and text flowing around it on the right.
+    /// No cut runs through the whole page, but the gutter between caption and text is found.
+    #[test]
+    fn test_l_shaped() {
+        let mut boxes = vec![];
+        for k in 0..4 {
+            let y = 100.0 + 12.0 * k as f32;
+            boxes.push((rect(50.0, y, 550.0, y + 10.0), boxes.len()));
+        }
+        let top = boxes.len();
+        for k in 0..6 {
+            let y = 160.0 + 12.0 * k as f32;
+            boxes.push((rect(50.0, y, 250.0, y + 10.0), boxes.len()));
+        }
+        for k in 0..6 {
+            let y = 160.0 + 12.0 * k as f32;
+            boxes.push((rect(280.0, y, 550.0, y + 10.0), boxes.len()));
+        }
+        // the last line runs below the figure, over the whole width
+        boxes.push((rect(50.0, 232.0, 550.0, 242.0), boxes.len()));
+
+        let content = boxes.iter().map(|&(r, _)| r).reduce(|a, b| a.union_rect(b)).unwrap();
+        let separators = separators(&boxes, content, 10.0);
+        assert_eq!(separators.len(), 1);
+        assert_eq!(separators[0].min_x(), 250.0);
+        assert_eq!(separators[0].max_x(), 280.0);
+
+        let spans: &[TextSpan<OutlineBuilder>] = &[];
+        let node = split_blocks(&mut boxes, spans, &analyze_lines(&[]));
+        let mut indices = vec![];
+        node.indices(&mut indices);
+        // the left block is read before the right one
+        assert_eq!(indices[top..top + 6], [4, 5, 6, 7, 8, 9]);
+        assert_eq!(indices[top + 6..top + 12], [10, 11, 12, 13, 14, 15]);
+    }
+}
@@ -0,0 +1,272 @@
+use font::Encoder;
+use pathfinder_geometry::rect::RectF;
+use pdf::object::{Counter, PageLabel, Resolve};
+use pdf_render::TextSpan;
+use serde::{Serialize, Deserialize};
+
+use crate::flow::Rect;
+use crate::text::concat_text;
+use crate::util::{parse_roman, to_roman};
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum NumberStyle {
+    Arabic,
+    RomanLower,
+    RomanUpper,
+}
+
+/// The page number printed on the page.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PageNumber {
+    /// The text as printed, e.g. "iv" or "Page 3 of 10"
+    pub text: String,
+    pub value: u32,
+    pub style: NumberStyle,
+    /// The number of pages, for "Page 3 of 10" or "3 / 10"
+    pub total: Option<u32>,
+    pub rect: Rect,
+    /// Whether it agrees with the page label from the catalog, `None` if there is no label.
+    pub matches_label: Option<bool>,
+}
+impl PageNumber {
+    /// The number in its style, e.g. "iv", the way page labels are written.
+    pub fn label(&self) -> String {
+        match self.style {
+            NumberStyle::Arabic => self.value.to_string(),
+            NumberStyle::RomanLower => to_roman(self.value),
+            NumberStyle::RomanUpper => to_roman(self.value).to_uppercase(),
+        }
+    }
+}
+
+/// Parse "3", "iv", "- 3 -", "Page 3", "Page 3 of 10", "p. 3", "3 / 10".
+pub fn parse(text: &str) -> Option<(u32, NumberStyle, Option<u32>)> {
+    let words: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || matches!(c, '-' | '–' | '—' | '|' | '[' | ']'))
+        .filter(|w| w.len() > 0)
+        .collect();
+
+    let number = |s: &str| -> Option<(u32, NumberStyle)> {
+        if s.len() <= 4 && s.chars().all(|c| c.is_ascii_digit()) {
+            return Some((s.parse().ok()?, NumberStyle::Arabic));
+        }
+        let n = parse_roman(s)?;
+        match s.chars().next()?.is_ascii_uppercase() {
+            true => Some((n, NumberStyle::RomanUpper)),
+            false => Some((n, NumberStyle::RomanLower)),
+        }
+    };
+    let is_page_word = |s: &str| matches!(s.to_lowercase().trim_end_matches('.'), "page" | "p" | "pg" | "seite" | "s" | "pagina" | "página");
+    let is_of_word = |s: &str| matches!(s.to_lowercase().as_str(), "of" | "/" | "von" | "de" | "di");
+
+    let words = match words.first() {
+        Some(w) if is_page_word(w) => &words[1..],
+        _ => &words[..],
+    };
+    match *words {
+        [n] => {
+            // "3/10"
+            if let Some((n, total)) = n.split_once('/') {
+                let (n, style) = number(n)?;
+                let (total, _) = number(total)?;
+                return Some((n, style, Some(total)));
+            }
+            let (n, style) = number(n)?;
+            Some((n, style, None))
+        }
+        [n, of, total] if is_of_word(of) => {
+            let (n, style) = number(n)?;
+            let (total, _) = number(total)?;
+            Some((n, style, Some(total)))
+        }
+        _ => None,
+    }
+}
+
+/// Find the page number in the top or bottom margin of the page.
+///
+/// The spans are grouped into segments that are separated from other text on the same line.
+/// A segment is a candidate if it parses as page number, no text lies between it and the edge of the page
+/// and the rest of its line, if any, is far away, so the last row of a table is not taken for it.
+/// Numbers beyond `page_count` are left out unless they agree with `label`, they are more likely a year like "© 2024".
+/// Of the candidates, the one closest to the edge of the page wins, unless one of them agrees with `label`.
+/// `bbox` is the whole page, not a region of it.
+/// Returns the page number and the indices of its spans.
+pub fn detect<E: Encoder>(spans: &[TextSpan<E>], bbox: RectF, label: Option<&str>, page_count: Option<u32>) -> Option<(PageNumber, Vec<usize>)> {
+    let band = 0.12 * bbox.height();
+    let mut margin: Vec<usize> = (0..spans.len())
+        .filter(|&i| {
+            let r = spans[i].rect;
+            r.max_y() < bbox.min_y() + band || r.min_y() > bbox.max_y() - band
+        })
+        .collect();
+    margin.sort_by(|&a, &b| spans[a].rect.min_x().partial_cmp(&spans[b].rect.min_x()).unwrap());
+
+    // group into segments: same row, close to each other
+    let mut segments: Vec<(RectF, Vec<usize>)> = vec![];
+    for i in margin {
+        let r = spans[i].rect;
+        let gap = 2.0 * spans[i].font_size.max(r.height());
+        let segment = segments.iter_mut().find(|(s, _)| {
+            r.min_y() < s.max_y() && s.min_y() < r.max_y() && r.min_x() < s.max_x() + gap
+        });
+        match segment {
+            Some((s, indices)) => {
+                *s = s.union_rect(r);
+                indices.push(i);
+            }
+            None => segments.push((r, vec![i])),
+        }
+    }
+
+    let mut candidates: Vec<(PageNumber, Vec<usize>, f32)> = segments.into_iter()
+        .filter_map(|(rect, indices)| {
+            let mut text = String::new();
+            let words = concat_text(&mut text, indices.iter().map(|&i| &spans[i]));
+            let text = words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" ");
+            let (value, style, total) = parse(&text)?;
+
+            let size = indices.iter().map(|&i| spans[i].font_size.max(spans[i].rect.height())).fold(0.0, f32::max);
+            let bottom = rect.min_y() > bbox.max_y() - band;
+            let others = (0..spans.len()).filter(|i| !indices.contains(i)).map(|i| spans[i].rect);
+            for r in others {
+                let beyond = match bottom {
+                    true => r.min_y() >= rect.max_y(),
+                    false => r.max_y() <= rect.min_y(),
+                };
+                let same_row = r.min_y() < rect.max_y() && rect.min_y() < r.max_y();
+                let near = r.min_x() < rect.max_x() + 4.0 * size && rect.min_x() < r.max_x() + 4.0 * size;
+                if beyond || (same_row && near) {
+                    return None;
+                }
+            }
+
+            let edge_dist = (rect.min_y() - bbox.min_y()).min(bbox.max_y() - rect.max_y());
+            let number = PageNumber { text, value, style, total, rect: rect.into(), matches_label: None };
+            Some((number, indices, edge_dist))
+        })
+        .collect();
+
+    if let Some(label) = label {
+        for (number, _, _) in candidates.iter_mut() {
+            number.matches_label = Some(number.label() == label || number.text == label);
+        }
+    }
+    if let Some(count) = page_count {
+        candidates.retain(|(number, _, _)| number.value <= count || number.matches_label == Some(true));
+    }
+    candidates.sort_by(|a, b| {
+        let matches = |c: &(PageNumber, Vec<usize>, f32)| c.0.matches_label == Some(true);
+        matches(b).cmp(&matches(a)).then(a.2.partial_cmp(&b.2).unwrap())
+    });
+    candidates.into_iter().next().map(|(number, indices, _)| (number, indices))
+}
+
+/// A range of `/PageLabels`: the index of its first page, the prefix, the numbering style and the first number.
+type LabelRange = (i32, String, Option<Counter>, usize);
+
+/// The label of the page with the given index from the catalog's `/PageLabels`.
+pub fn page_label(labels: &pdf::object::NumberTree<PageLabel>, resolve: &impl Resolve, page_nr: u32) -> Option<String> {
+    let mut ranges: Vec<LabelRange> = vec![];
+    labels.walk(resolve, &mut |start, label| {
+        let prefix = label.prefix.as_ref().map(|p| p.to_string_lossy()).unwrap_or_default();
+        ranges.push((start, prefix, label.style, label.start.unwrap_or(1)));
+    }).ok()?;
+    range_label(&ranges, page_nr)
+}
+
+fn range_label(ranges: &[LabelRange], page_nr: u32) -> Option<String> {
+    // the range with the highest start index that is not after the page
+    let (start, prefix, style, first) = ranges.iter()
+        .filter(|r| r.0 <= page_nr as i32)
+        .max_by_key(|r| r.0)?;
+    let n = (page_nr as i32 - start) as usize + first;
+    let number = match style {
+        None => String::new(),
+        Some(Counter::Arabic) => n.to_string(),
+        Some(Counter::RomanLower) => to_roman(n as u32),
+        Some(Counter::RomanUpper) => to_roman(n as u32).to_uppercase(),
+        Some(Counter::AlphaLower) => alpha(n),
+        Some(Counter::AlphaUpper) => alpha(n).to_uppercase(),
+    };
+    Some(prefix.clone() + &number)
+}
+
+/// 1 → "a", 26 → "z", 27 → "aa", 28 → "bb", and nothing for 0
+fn alpha(n: usize) -> String {
+    if n == 0 {
+        return String::new();
+    }
+    let letter = (b'a' + ((n - 1) % 26) as u8) as char;
+    std::iter::repeat(letter).take((n - 1) / 26 + 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("3"), Some((3, NumberStyle::Arabic, None)));
+        assert_eq!(parse("- 12 -"), Some((12, NumberStyle::Arabic, None)));
+        assert_eq!(parse("iv"), Some((4, NumberStyle::RomanLower, None)));
+        assert_eq!(parse("XII"), Some((12, NumberStyle::RomanUpper, None)));
+        assert_eq!(parse("Page 3 of 10"), Some((3, NumberStyle::Arabic, Some(10))));
+        assert_eq!(parse("p. 7"), Some((7, NumberStyle::Arabic, None)));
+        assert_eq!(parse("3/10"), Some((3, NumberStyle::Arabic, Some(10))));
+        assert_eq!(parse("3 / 10"), Some((3, NumberStyle::Arabic, Some(10))));
+
+        assert_eq!(parse("Chapter 3"), None);
+        assert_eq!(parse("2024 Annual Report"), None);
+        assert_eq!(parse("12345"), None);
+    }
+
+    #[test]
+    fn test_range_label() {
+        let ranges = [
+            (0, String::new(), Some(Counter::RomanLower), 1),
+            (4, String::new(), Some(Counter::Arabic), 1),
+            (10, "A-".to_string(), Some(Counter::Arabic), 8),
+        ];
+        assert_eq!(range_label(&ranges, 2).as_deref(), Some("iii"));
+        assert_eq!(range_label(&ranges, 4).as_deref(), Some("1"));
+        assert_eq!(range_label(&ranges, 9).as_deref(), Some("6"));
+        assert_eq!(range_label(&ranges, 11).as_deref(), Some("A-9"));
+        assert_eq!(range_label(&ranges[1..], 2), None);
+    }
+
+    #[test]
+    fn test_alpha() {
+        assert_eq!(alpha(1), "a");
+        assert_eq!(alpha(26), "z");
+        assert_eq!(alpha(28), "bb");
+        assert_eq!(alpha(0), "");
+    }
+
+    #[test]
+    fn test_detect() {
+        use crate::testing::span;
+        use pathfinder_geometry::vector::Vector2F;
+
+        let page = RectF::from_points(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0));
+        let body = || span("Some text on the page", 50.0, 400.0, 10.0);
+
+        let (number, indices) = detect(&[body(), span("7", 300.0, 770.0, 10.0)], page, None, Some(10)).unwrap();
+        assert_eq!(number.value, 7);
+        assert_eq!(indices, [1]);
+
+        // the last row of a table that runs into the margin
+        assert!(detect(&[body(), span("Total", 200.0, 770.0, 10.0), span("7", 245.0, 770.0, 10.0)], page, None, Some(10)).is_none());
+        // a running footer far from the number is fine
+        let footer = [body(), span("Annual Report", 50.0, 770.0, 10.0), span("7", 540.0, 770.0, 10.0)];
+        assert_eq!(detect(&footer, page, None, Some(10)).unwrap().1, [2]);
+        // a number with more text below it is not at the edge
+        assert!(detect(&[body(), span("7", 300.0, 750.0, 10.0), span("Confidential", 270.0, 780.0, 10.0)], page, None, Some(10)).is_none());
+
+        // a year beyond the page count, unless it is the label
+        let year = [body(), span("2024", 300.0, 770.0, 10.0)];
+        assert!(detect(&year, page, None, Some(10)).is_none());
+        assert_eq!(detect(&year, page, Some("2024"), Some(10)).unwrap().0.value, 2024);
+        assert_eq!(detect(&year, page, None, None).unwrap().0.value, 2024);
+    }
+}
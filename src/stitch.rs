@@ -0,0 +1,233 @@
+use serde::{Serialize, Deserialize};
+
+use crate::flow::{CellContent, Flow, HeaderCell, RunType, TableContent};
+
+/// A table put together from its parts on consecutive pages.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StitchedTable {
+    pub columns: usize,
+    /// The number of rows at the top of `rows` that form the header, those of the first part
+    pub header_rows: usize,
+    pub header: Vec<HeaderCell<CellContent>>,
+    pub rows: Vec<StitchedRow>,
+}
+
+/// A row of a stitched table. The `row` of its cells counts from the top of the whole table.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StitchedRow {
+    /// Index of the page in the flows passed to [`stitch`]
+    pub page: usize,
+    /// Index of the part in `Flow::tables` of that page
+    pub table: usize,
+    pub cells: Vec<CellContent>,
+}
+
+/// Put tables that continue over several pages back together.
+///
+/// `pages` are the flows of consecutive pages. The first table of a page continues the last table
+/// of the page before if no text comes between them, it has the same columns in the same places,
+/// and it repeats the header of the table or has none. The repeated header rows are left out.
+/// Every other table is a table of its own.
+pub fn stitch(pages: &[Flow]) -> Vec<StitchedTable> {
+    let mut out: Vec<StitchedTable> = vec![];
+    // the stitched table that may continue on the next page, with its last part
+    let mut open: Option<(usize, Vec<Vec<CellContent>>)> = None;
+
+    for (page, flow) in pages.iter().enumerate() {
+        let mut prev = open.take();
+        for (t, table) in flow.tables.iter().enumerate() {
+            let rows = grid(table);
+            let continued = match prev.take() {
+                Some((i, last)) if t == 0 && !text_before(flow, table)
+                    && same_columns(&last, &rows)
+                    && (table.header_rows == 0 || repeats_header(&out[i], &rows[..table.header_rows])) => Some(i),
+                _ => None,
+            };
+
+            let i = match continued {
+                Some(i) => {
+                    append(&mut out[i], page, t, &rows[table.header_rows..]);
+                    i
+                }
+                None => {
+                    let mut stitched = StitchedTable {
+                        columns: table.columns,
+                        header_rows: table.header_rows,
+                        header: table.header.clone(),
+                        rows: vec![],
+                    };
+                    append(&mut stitched, page, t, &rows);
+                    out.push(stitched);
+                    out.len() - 1
+                }
+            };
+            if t + 1 == flow.tables.len() && !text_after(flow, table) {
+                open = Some((i, rows));
+            }
+        }
+    }
+    out
+}
+
+/// The cells of the table by row.
+fn grid(table: &TableContent) -> Vec<Vec<CellContent>> {
+    let mut rows = vec![vec![]; table.rows];
+    for cell in table.table.values() {
+        rows[cell.value.row].push(cell.value.clone());
+    }
+    for row in rows.iter_mut() {
+        row.sort_by_key(|c| c.col);
+    }
+    rows
+}
+
+fn append(stitched: &mut StitchedTable, page: usize, table: usize, rows: &[Vec<CellContent>]) {
+    let base = stitched.rows.len();
+    let first = rows.iter().flatten().map(|c| c.row).min().unwrap_or(0);
+    for row in rows {
+        let cells = row.iter().cloned()
+            .map(|mut c| {
+                c.row = base + c.row - first;
+                c
+            })
+            .collect();
+        stitched.rows.push(StitchedRow { page, table, cells });
+    }
+}
+
+fn is_text(kind: &RunType) -> bool {
+    matches!(kind, RunType::Paragraph | RunType::ParagraphContinuation | RunType::ListItem { .. })
+}
+
+/// Whether body text comes before the table on its page. Running headers and captions do not count.
+fn text_before(flow: &Flow, table: &TableContent) -> bool {
+    flow.runs.iter().any(|r| is_text(&r.kind) && r.bbox().map_or(false, |b| b.max_y() <= table.rect.y))
+}
+
+/// Whether body text comes after the table on its page. Footnotes and captions do not count.
+fn text_after(flow: &Flow, table: &TableContent) -> bool {
+    flow.runs.iter().any(|r| is_text(&r.kind) && r.bbox().map_or(false, |b| b.min_y() >= table.rect.y + table.rect.h))
+}
+
+/// The x range of each column, from the cells that span only that column.
+fn column_ranges(rows: &[Vec<CellContent>]) -> Vec<Option<(f32, f32)>> {
+    let columns = rows.iter().flatten().map(|c| c.col + c.colspan).max().unwrap_or(0);
+    let mut ranges: Vec<Option<(f32, f32)>> = vec![None; columns];
+    for cell in rows.iter().flatten().filter(|c| c.colspan == 1) {
+        let (min, max) = (cell.rect.x, cell.rect.x + cell.rect.w);
+        let range = &mut ranges[cell.col];
+        *range = Some(range.map_or((min, max), |(a, b)| (a.min(min), b.max(max))));
+    }
+    ranges
+}
+
+/// Whether both have the same number of columns, and the columns known in both overlap.
+fn same_columns(a: &[Vec<CellContent>], b: &[Vec<CellContent>]) -> bool {
+    let (a, b) = (column_ranges(a), column_ranges(b));
+    a.len() == b.len() && a.len() > 0
+        && a.iter().zip(b.iter()).all(|pair| match pair {
+            (Some((a1, a2)), Some((b1, b2))) => a1 <= b2 && b1 <= a2,
+            _ => true,
+        })
+}
+
+/// Whether the rows have the same text as the header rows of the table.
+fn repeats_header(stitched: &StitchedTable, header: &[Vec<CellContent>]) -> bool {
+    let texts = |rows: &mut dyn Iterator<Item=&Vec<CellContent>>| -> Vec<(usize, String)> {
+        rows.enumerate()
+            .flat_map(|(r, row)| row.iter().map(move |c| (r, c.text.trim().to_string())))
+            .collect()
+    };
+    let own = texts(&mut stitched.rows[..stitched.header_rows].iter().map(|r| &r.cells));
+    own.len() > 0 && own == texts(&mut header.iter())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{Flow, Line, Rect, Run};
+    use crate::testing::{analyze, content, span, table, table_spans, words};
+
+    fn row(r: usize, cells: &[(&str, f32)]) -> Vec<CellContent> {
+        cells.iter().enumerate().map(|(col, &(text, x))| CellContent {
+            text: text.into(),
+            lines: vec![],
+            rect: Rect { x, y: 20.0 * r as f32, w: 40.0, h: 10.0 },
+            grid_rect: Rect { x, y: 20.0 * r as f32, w: 50.0, h: 20.0 },
+            value: None,
+            font_size: 10.0,
+            bold: false,
+            nested: None,
+            figures: vec![],
+            row: r,
+            col,
+            rowspan: 1,
+            colspan: 1,
+        }).collect()
+    }
+
+    #[test]
+    fn test_continuation() {
+        let first = vec![row(0, &[("Item", 0.0), ("Amount", 100.0)]), row(1, &[("Rent", 0.0), ("1,200", 110.0)])];
+        let next = vec![row(0, &[("Item", 0.0), ("Amount", 100.0)]), row(1, &[("Food", 0.0), ("300", 120.0)])];
+        let other = vec![row(0, &[("Name", 0.0), ("Age", 50.0)])];
+        assert!(same_columns(&first, &next));
+        assert!(!same_columns(&first, &other));
+
+        let mut stitched = StitchedTable { columns: 2, header_rows: 1, header: vec![], rows: vec![] };
+        append(&mut stitched, 0, 0, &first);
+        assert!(repeats_header(&stitched, &next[..1]));
+        assert!(!repeats_header(&stitched, &other[..1]));
+
+        append(&mut stitched, 1, 0, &next[1..]);
+        assert_eq!(stitched.rows.len(), 3);
+        assert_eq!((stitched.rows[2].page, stitched.rows[2].cells[0].row), (1, 2));
+    }
+
+    fn paragraph(text: &str, y: f32) -> Run {
+        Run { lines: vec![Line { words: words(text, 50.0, y, 10.0), rect: Rect { x: 50.0, y, w: 300.0, h: 10.0 } }], kind: RunType::Paragraph }
+    }
+
+    #[test]
+    fn test_stitch() {
+        let header: &[&str] = &["Fruit", "Price", "Qty"];
+        let mut first = Flow::new();
+        first.runs.push(paragraph("The prices of the fruit we sold last week are these:", 100.0));
+        first.tables.push(table(&[header, &["Apples", "1.20", "4"], &["Pears", "0.80", "12"]], 50.0, 600.0, 1));
+        let mut second = Flow::new();
+        second.tables.push(table(&[header, &["Cherries", "4.10", "2"], &["Grapes", "3.30", "5"]], 50.0, 80.0, 1));
+        second.runs.push(paragraph("Grapes sold best in the autumn.", 300.0));
+        let mut third = Flow::new();
+        third.tables.push(table(&[&["Cherries", "4.10"]], 50.0, 80.0, 0));
+
+        let tables = stitch(&[first, second, third]);
+        assert_eq!(tables.len(), 2);
+        let table = &tables[0];
+        assert_eq!((table.columns, table.header_rows), (3, 1));
+        // the header is not repeated
+        let first_column: Vec<&str> = table.rows.iter().map(|r| r.cells[0].text.as_str()).collect();
+        assert_eq!(first_column, ["Fruit", "Apples", "Pears", "Cherries", "Grapes"]);
+        assert_eq!(table.rows.iter().map(|r| r.page).collect::<Vec<_>>(), [0, 0, 0, 1, 1]);
+        assert_eq!(table.rows[4].cells[2].row, 4);
+        // text after the table on the second page ends it
+        assert_eq!(tables[1].rows[0].page, 2);
+    }
+
+    #[test]
+    fn test_pages() {
+        let header = ["Fruit", "Price", "Qty"];
+        let mut first = table_spans(&[header, ["Apples", "1.20", "4"], ["Pears", "0.80", "12"], ["Plums", "2.50", "7"]], 600.0);
+        first.push(span("The prices of the fruit we sold last week are these:", 150.0, 540.0, 10.0));
+        let second = table_spans(&[header, ["Cherries", "4.10", "2"], ["Grapes", "3.30", "5"]], 100.0);
+
+        let pages: Vec<Flow> = [first, second].into_iter().map(|spans| analyze(content(spans, &[])).flow).collect();
+        let tables = stitch(&pages);
+        assert_eq!(tables.len(), 1);
+        let table = &tables[0];
+        assert_eq!(table.header_rows, 1);
+        // the header is not repeated
+        let first_column: Vec<&str> = table.rows.iter().map(|r| r.cells[0].text.as_str()).collect();
+        assert_eq!(first_column, ["Fruit", "Apples", "Pears", "Plums", "Cherries", "Grapes"]);
+        assert_eq!(table.rows.iter().map(|r| r.page).collect::<Vec<_>>(), [0, 0, 0, 0, 1, 1]);
+    }
+}
@@ -0,0 +1,127 @@
+//! Fixtures shared by the unit tests.
+
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_geometry::{rect::RectF, transform2d::Transform2F, vector::Vector2F};
+use pdf_render::{font::OutlineBuilder, Fill, TextChar, TextSpan};
+
+use crate::flow::{CellContent, Line, Rect, TableContent, VerticalPosition, Word};
+use crate::node::Node;
+use crate::{analyze_content, Analysis, Content, ExtractOptions};
+
+/// A black span with its baseline at `baseline`, each char half as wide as the font size.
+pub fn span(text: &str, x: f32, baseline: f32, size: f32) -> TextSpan<OutlineBuilder> {
+    let width = 0.5 * size;
+    TextSpan {
+        rect: RectF::from_points(Vector2F::new(x, baseline - size), Vector2F::new(x + width * text.len() as f32, baseline)),
+        width: width * text.len() as f32,
+        bbox: None,
+        font_size: size,
+        font: None,
+        text: text.to_string(),
+        chars: (0..text.len()).map(|i| TextChar { offset: i, pos: width * i as f32, width }).collect(),
+        color: Fill::Solid(0.0, 0.0, 0.0),
+        alpha: 1.0,
+        transform: Transform2F::from_translation(Vector2F::new(x, baseline)),
+        mode: pdf::content::TextMode::Fill,
+        op_nr: 0,
+    }
+}
+
+/// The space separated words of `text` laid out from `x`, with the same metrics as [span].
+pub fn words(text: &str, x: f32, y: f32, size: f32) -> Vec<Word> {
+    let width = 0.5 * size;
+    let mut word_x = x;
+    text.split(' ').map(|w| {
+        let rect = Rect { x: word_x, y, w: width * w.len() as f32, h: size };
+        word_x += rect.w + width;
+        Word { text: w.into(), rect, chars: vec![], value: None, position: VerticalPosition::Baseline }
+    }).collect()
+}
+
+/// A table of one line cells, 100 wide and 20 high, with its top left corner at `x`, `y`.
+pub(crate) fn table(rows: &[&[&str]], x: f32, y: f32, header_rows: usize) -> TableContent {
+    let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut table = ::table::Table::empty(rows.len() as u32, columns as u32);
+    for (row, texts) in rows.iter().enumerate() {
+        for (col, &text) in texts.iter().enumerate() {
+            let rect = Rect { x: x + 100.0 * col as f32, y: y + 20.0 * row as f32, w: 5.0 * text.len() as f32, h: 10.0 };
+            let cell = CellContent {
+                text: text.into(),
+                lines: vec![Line { words: words(text, rect.x, rect.y, 10.0), rect }],
+                rect,
+                grid_rect: Rect { w: 100.0, h: 20.0, ..rect },
+                value: crate::value::parse(text),
+                font_size: 10.0,
+                bold: false,
+                nested: None,
+                figures: vec![],
+                row,
+                col,
+                rowspan: 1,
+                colspan: 1,
+            };
+            table.set_cell(cell, row as u32, col as u32, 1, 1);
+        }
+    }
+    TableContent {
+        table,
+        rect: Rect { x, y, w: 100.0 * columns as f32, h: 20.0 * rows.len() as f32 },
+        rows: rows.len(),
+        columns,
+        header_rows,
+        header: vec![],
+    }
+}
+
+/// The page the content of the analysis tests is laid out on.
+pub(crate) fn page() -> RectF {
+    RectF::new(Vector2F::new(0.0, 0.0), Vector2F::new(600.0, 800.0))
+}
+
+/// The page content of `spans` and the drawn `lines`.
+pub(crate) fn content(spans: Vec<TextSpan<OutlineBuilder>>, lines: &[[f32; 4]]) -> Content {
+    Content { spans, lines: lines.to_vec(), ..Content::default() }
+}
+
+/// The analysis of `content` on the [page] with the default options.
+pub(crate) fn analyze(content: Content) -> Analysis {
+    analyze_content(content, page(), None, None, &ExtractOptions::default())
+}
+
+/// A table with a text column and two of numbers, a row every 30 points from the baseline `y` on.
+pub(crate) fn table_spans(rows: &[[&str; 3]], y: f32) -> Vec<TextSpan<OutlineBuilder>> {
+    rows.iter().enumerate()
+        .flat_map(|(r, row)| {
+            let baseline = y + 30.0 * r as f32;
+            [span(row[0], 150.0, baseline, 10.0), span(row[1], 300.0, baseline, 10.0), span(row[2], 420.0, baseline, 10.0)]
+        })
+        .collect()
+}
+
+/// The first table in the layout tree.
+pub(crate) fn table_node(node: &Node) -> Option<&Node> {
+    match node {
+        Node::Table { .. } => Some(node),
+        Node::Grid { cells, .. } => cells.iter().find_map(table_node),
+        Node::Final { .. } => None,
+    }
+}
+
+/// The outline of `rect` with its corners rounded off by quadratic curves of radius `r`.
+pub(crate) fn rounded_rect(rect: RectF, r: f32) -> Outline {
+    let (a, b) = (rect.origin(), rect.lower_right());
+    let mut contour = Contour::new();
+    contour.push_endpoint(Vector2F::new(a.x() + r, a.y()));
+    contour.push_endpoint(Vector2F::new(b.x() - r, a.y()));
+    contour.push_quadratic(Vector2F::new(b.x(), a.y()), Vector2F::new(b.x(), a.y() + r));
+    contour.push_endpoint(Vector2F::new(b.x(), b.y() - r));
+    contour.push_quadratic(b, Vector2F::new(b.x() - r, b.y()));
+    contour.push_endpoint(Vector2F::new(a.x() + r, b.y()));
+    contour.push_quadratic(Vector2F::new(a.x(), b.y()), Vector2F::new(a.x(), b.y() - r));
+    contour.push_endpoint(Vector2F::new(a.x(), a.y() + r));
+    contour.push_quadratic(a, Vector2F::new(a.x() + r, a.y()));
+    contour.close();
+    let mut outline = Outline::new();
+    outline.push_contour(contour);
+    outline
+}
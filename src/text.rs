@@ -5,10 +5,11 @@ use pathfinder_geometry::vector::Vector2F;
 use pdf_render::TextSpan;
 use itertools::Itertools;
 use unicode_normalization::UnicodeNormalization;
-use crate::{flow::{Char, Rect, Word}, util::avg};
+use crate::{flow::{Char, Rect, VerticalPosition, Word}, util::{avg, median_span}, value};
 
 pub fn concat_text<'a, E: Encoder + 'a>(out: &mut String, items: impl Iterator<Item=&'a TextSpan<E>> + Clone) -> Vec<Word> {
     let word_gap = analyze_word_gap(items.clone());
+    let reference = Reference::new(items.clone());
     let mut words = Vec::new();
     let mut current_word = WordBuilder::new(out.len(), 0.0);
     
@@ -23,6 +24,7 @@ pub fn concat_text<'a, E: Encoder + 'a>(out: &mut String, items: impl Iterator<I
     let mut end = 0.; // trailing edge of the last char
 
     for span in items {
+        let span_position = reference.as_ref().map_or(VerticalPosition::Baseline, |r| r.position(span));
         let mut offset = 0;
         let tr_inv = span.transform.matrix.inverse();
         // Device space to em space
@@ -45,6 +47,7 @@ pub fn concat_text<'a, E: Encoder + 'a>(out: &mut String, items: impl Iterator<I
             let char_end = (span.transform * Vector2F::new(current.pos + current.width, 0.0)).x();
             
             let is_whitespace = text.chars().all(|c| c.is_whitespace());
+            let position = text.chars().next().and_then(unicode_position).unwrap_or(span_position);
            
             // byte offsets
             let bytes_offset = text.len();
@@ -52,7 +55,7 @@ pub fn concat_text<'a, E: Encoder + 'a>(out: &mut String, items: impl Iterator<I
             if trailing_space && !is_whitespace {
                 // Start new word after space
                 current_word = WordBuilder::new(out.len(),char_start);
-                current_word.add_char(bytes_offset, char_start, char_end);
+                current_word.add_char(bytes_offset, char_start, char_end, position);
 
                 out.push_str(text);
             } else if !trailing_space {
@@ -68,12 +71,12 @@ pub fn concat_text<'a, E: Encoder + 'a>(out: &mut String, items: impl Iterator<I
                     words.push(current_word.build(out));
 
                     current_word = WordBuilder::new(out.len(), char_start);
-                    current_word.add_char(bytes_offset, char_start, char_end);
+                    current_word.add_char(bytes_offset, char_start, char_end, position);
 
                     out.push_str(text);
                 } else {
                     // Continue current word
-                    current_word.add_char(bytes_offset, char_start, char_end);
+                    current_word.add_char(bytes_offset, char_start, char_end, position);
 
                     // out.extend(text.nfkc()); // nfkc will change the bytes length of a char.
                     out.push_str(text);
@@ -126,11 +129,12 @@ impl WordBuilder {
         }
     }
 
-    fn add_char(&mut self, bytes_offset: usize, start: f32, end: f32) {
+    fn add_char(&mut self, bytes_offset: usize, start: f32, end: f32, position: VerticalPosition) {
         self.chars.push(Char {
             offset: self.bytes_offset,
             pos: start,
             width: end - start,
+            position,
         });
         self.end_pos = end;
 
@@ -154,8 +158,18 @@ impl WordBuilder {
     }
 
     fn build(mut self, out: &str) -> Word {
+        let text = &out[self.word_start_idx..];
+        // mixed words like "m²" are on the baseline, only their chars are marked
+        let position = match self.chars.first() {
+            Some(first) if self.chars.iter().all(|c| c.position == first.position) => first.position,
+            _ => VerticalPosition::Baseline,
+        };
+        // "10⁶" is no 106
+        let on_baseline = self.chars.iter().all(|c| c.position == VerticalPosition::Baseline);
         Word {
-            text: out[self.word_start_idx..].into(),
+            text: text.into(),
+            value: on_baseline.then(|| value::parse(text)).flatten(),
+            position,
             rect: Rect {
                 x: self.start_pos,
                 y: self.y_min,
@@ -167,6 +181,47 @@ impl WordBuilder {
     }
 }
 
+/// Baseline and height of the dominant text of a line, superscripts and subscripts are relative to it.
+struct Reference {
+    baseline: f32,
+    height: f32,
+}
+impl Reference {
+    /// Takes the span at the median height, weighted by the number of chars.
+    fn new<'a, E: Encoder + 'a>(items: impl Iterator<Item=&'a TextSpan<E>>) -> Option<Self> {
+        median_span(items).map(|s| Reference { baseline: s.transform.vector.y(), height: s.rect.height() })
+    }
+
+    fn position<E: Encoder>(&self, span: &TextSpan<E>) -> VerticalPosition {
+        if span.rect.height() > 0.9 * self.height {
+            return VerticalPosition::Baseline;
+        }
+        // device space grows downwards, a raised baseline has a smaller y
+        let offset = self.baseline - span.transform.vector.y();
+        if offset.abs() > 0.8 * self.height {
+            // that is another line
+            VerticalPosition::Baseline
+        } else if offset > 0.15 * self.height {
+            VerticalPosition::Superscript
+        } else if offset < -0.1 * self.height {
+            VerticalPosition::Subscript
+        } else {
+            VerticalPosition::Baseline
+        }
+    }
+}
+
+/// Unicode superscript and subscript characters, like "²" or "₂".
+fn unicode_position(c: char) -> Option<VerticalPosition> {
+    match c {
+        // ¹ ² ³ and the block from ⁰ to ⁿ
+        '\u{b9}' | '\u{b2}' | '\u{b3}' | '\u{2070}' ..= '\u{207f}' => Some(VerticalPosition::Superscript),
+        // ₀ to ₜ
+        '\u{2080}' ..= '\u{209c}' => Some(VerticalPosition::Subscript),
+        _ => None
+    }
+}
+
 /// Calculate gaps between each char, the return value unit is em
 
 /// The most important thing here is to make sure the gap is bigger than char gap, and less than word gap.
@@ -282,4 +337,31 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_superscript() {
+        use crate::testing::span;
+        let spans = [
+            span("10", 50.0, 100.0, 12.0),
+            span("6", 62.0, 95.0, 7.0),
+            span(" H", 65.5, 100.0, 12.0),
+            span("2", 77.5, 103.0, 7.0),
+            span("O", 81.0, 100.0, 12.0),
+        ];
+
+        let mut output = String::new();
+        let words = concat_text(&mut output, spans.iter());
+
+        assert_eq!(output, "106 H2O");
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].chars[2].position, VerticalPosition::Superscript);
+        assert_eq!(words[0].position, VerticalPosition::Baseline);
+        assert_eq!(words[0].marked_text(), "10^6");
+        assert_eq!(words[0].value, None);
+        assert_eq!(words[1].marked_text(), "H_2O");
+
+        let mut output = String::new();
+        let words = concat_text(&mut output, spans[..1].iter());
+        assert_eq!(words[0].value, Some(crate::value::Value::Number(10.0)));
+    }
 }
\ No newline at end of file
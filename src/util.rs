@@ -1,3 +1,6 @@
+use font::Encoder;
+use pdf_render::TextSpan;
+
 pub fn is_number(s: &str) -> bool {
     s.len() > 0 && s.chars().all(|c| ('0' ..= '9').contains(&c))
 }
@@ -14,4 +17,86 @@ pub fn avg(iter: impl Iterator<Item=f32>) -> Option<f32> {
     } else {
         None
     }
-}
\ No newline at end of file
+}
+
+/// The middle value, `None` if there are none.
+pub fn median(mut values: Vec<f32>) -> Option<f32> {
+    values.sort_by(f32::total_cmp);
+    values.get(values.len() / 2).cloned()
+}
+
+/// The span at the median height, weighted by the number of chars, the height of the body text.
+pub fn median_span<'a, E: Encoder + 'a>(spans: impl Iterator<Item=&'a TextSpan<E>>) -> Option<&'a TextSpan<E>> {
+    let mut spans: Vec<&TextSpan<E>> = spans.collect();
+    spans.sort_by(|a, b| a.rect.height().total_cmp(&b.rect.height()));
+    let total: usize = spans.iter().map(|s| s.chars.len()).sum();
+    let mut count = 0;
+    spans.into_iter().find(|s| {
+        count += s.chars.len();
+        2 * count >= total
+    })
+}
+
+/// Remove the items at the given indices, keeping the order of the others.
+pub fn remove_indices<T>(items: &mut Vec<T>, indices: &[usize]) {
+    let mut i = 0;
+    items.retain(|_| {
+        i += 1;
+        !indices.contains(&(i - 1))
+    });
+}
+
+/// The root of `i` in a union-find forest, halving the path on the way.
+pub fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Parse a roman numeral (either all upper or all lower case).
+/// Only the canonical spelling is accepted, so words like "did" are rejected.
+pub fn parse_roman(s: &str) -> Option<u32> {
+    if s.is_empty() || !(s.chars().all(|c| c.is_ascii_uppercase()) || s.chars().all(|c| c.is_ascii_lowercase())) {
+        return None;
+    }
+    let digit = |c: char| match c.to_ascii_lowercase() {
+        'i' => Some(1),
+        'v' => Some(5),
+        'x' => Some(10),
+        'l' => Some(50),
+        'c' => Some(100),
+        'd' => Some(500),
+        'm' => Some(1000),
+        _ => None
+    };
+    let digits: Vec<u32> = s.chars().map(digit).collect::<Option<_>>()?;
+    let mut value = 0;
+    for (i, &d) in digits.iter().enumerate() {
+        match digits.get(i + 1) {
+            Some(&next) if next > d => value -= d as i32,
+            _ => value += d as i32,
+        }
+    }
+    if value <= 0 || to_roman(value as u32) != s.to_ascii_lowercase() {
+        return None;
+    }
+    Some(value as u32)
+}
+
+pub fn to_roman(mut n: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"),
+        (100, "c"), (90, "xc"), (50, "l"), (40, "xl"),
+        (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i")
+    ];
+    let mut out = String::new();
+    for &(value, numeral) in NUMERALS.iter() {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
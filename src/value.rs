@@ -0,0 +1,281 @@
+use serde::{Serialize, Deserialize};
+
+use crate::util::parse_roman;
+
+/// A typed interpretation of a word or table cell.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Percentage(f64),
+    Currency {
+        amount: f64,
+        /// The symbol or ISO code as printed, e.g. "€" or "USD"
+        currency: String,
+    },
+    Date {
+        year: i32,
+        month: u32,
+        day: u32,
+    },
+    Roman(u32),
+}
+
+const CURRENCY_SYMBOLS: &[&str] = &["$", "€", "£", "¥", "₹", "₽", "₩", "₺", "₪", "₫", "₿", "¢", "Fr.", "kr"];
+
+/// ISO 4217 codes of the currencies in use, sorted.
+const CURRENCY_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BGN", "BHD", "BIF",
+    "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CLP", "CNY", "COP", "CRC",
+    "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS",
+    "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD",
+    "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL",
+    "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MYR", "MZN", "NAD",
+    "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD",
+    "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP",
+    "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS", "VES",
+    "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+/// Recognize numbers, currencies, percentages, dates and roman numerals.
+///
+/// Numbers may use either "," or "." as decimal separator, grouping by ",", ".", "'" or spaces,
+/// and negatives can be written as "-12", "12-" or in accounting style "(12)".
+/// Roman numerals need at least two upper case letters in canonical spelling, as lower case
+/// words like "mix" or "dim" and the pronoun "I" would pass otherwise.
+pub fn parse(s: &str) -> Option<Value> {
+    let s = s.trim().trim_end_matches(|c| matches!(c, ',' | ';' | ':'));
+    if s.is_empty() {
+        return None;
+    }
+    if let Some(date) = parse_date(s) {
+        return Some(date);
+    }
+    if s.len() >= 2 && s.chars().all(|c| c.is_ascii_uppercase()) {
+        if let Some(n) = parse_roman(s) {
+            return Some(Value::Roman(n));
+        }
+    }
+
+    let (s, parens) = match s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(inner) => (inner.trim(), true),
+        None => (s, false),
+    };
+    let (s, percent) = match s.strip_suffix('%') {
+        Some(s) => (s.trim_end(), true),
+        None => (s, false),
+    };
+    let (s, sign_before) = strip_sign(s);
+    let (s, currency) = strip_currency(s);
+    let (s, sign_after) = strip_sign(s);
+    let (s, trailing_minus) = match s.strip_suffix(|c| is_minus(c)) {
+        Some(s) => (s.trim_end(), true),
+        None => (s, false),
+    };
+    // "12." at the end of a sentence
+    let s = match s.strip_suffix('.') {
+        Some(t) if t.ends_with(|c: char| c.is_ascii_digit()) => t,
+        _ => s,
+    };
+
+    let mut number = parse_number(s)?;
+    if parens || sign_before || sign_after || trailing_minus {
+        number = -number;
+    }
+
+    Some(match (currency, percent) {
+        (Some(currency), false) => Value::Currency { amount: number, currency: currency.into() },
+        (None, true) => Value::Percentage(number),
+        (None, false) => Value::Number(number),
+        (Some(_), true) => return None,
+    })
+}
+
+/// Whether the text is a recognized typed value.
+pub fn is_value(s: &str) -> bool {
+    parse(s).is_some()
+}
+
+fn is_minus(c: char) -> bool {
+    matches!(c, '-' | '−' | '–')
+}
+
+fn strip_sign(s: &str) -> (&str, bool) {
+    match s.strip_prefix(is_minus) {
+        Some(s) => (s.trim_start(), true),
+        None => (s.strip_prefix('+').unwrap_or(s).trim_start(), false),
+    }
+}
+
+fn strip_currency(s: &str) -> (&str, Option<&str>) {
+    for &symbol in CURRENCY_SYMBOLS {
+        if let Some(rest) = s.strip_prefix(symbol) {
+            return (rest.trim_start(), Some(symbol));
+        }
+        if let Some(rest) = s.strip_suffix(symbol) {
+            return (rest.trim_end(), Some(symbol));
+        }
+    }
+    // ISO 4217 codes like "USD 40" or "40 EUR", but not "ISO 9001"
+    let is_code = |code: &str| CURRENCY_CODES.binary_search(&code).is_ok();
+    if let Some((code, rest)) = s.split_once(char::is_whitespace) {
+        if is_code(code) {
+            return (rest.trim_start(), Some(code));
+        }
+    }
+    if let Some((rest, code)) = s.rsplit_once(char::is_whitespace) {
+        if is_code(code) {
+            return (rest.trim_end(), Some(code));
+        }
+    }
+    (s, None)
+}
+
+fn is_group_separator(c: char) -> bool {
+    matches!(c, ' ' | '\u{a0}' | '\u{202f}' | '\'' | '’')
+}
+
+/// Parse an unsigned number with optional grouping and decimal separators.
+fn parse_number(s: &str) -> Option<f64> {
+    if !s.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == ',') || !s.ends_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if !s.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',' || is_group_separator(c)) {
+        return None;
+    }
+
+    let decimal = decimal_separator(s);
+
+    let (int_part, frac_part) = match decimal {
+        Some(sep) => {
+            let (int_part, frac_part) = s.rsplit_once(sep).unwrap();
+            (int_part, Some(frac_part))
+        }
+        None => (s, None),
+    };
+    if frac_part.map_or(false, |f| !f.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+
+    // every group after the first must have exactly three digits
+    let mut groups = int_part.split(|c| c == ',' || c == '.' || is_group_separator(c));
+    let first = groups.next().unwrap();
+    if first.len() > 3 && int_part.len() > first.len() {
+        return None;
+    }
+    let mut digits = String::from(first);
+    for group in groups {
+        if group.len() != 3 {
+            return None;
+        }
+        digits.push_str(group);
+    }
+    if digits.is_empty() {
+        digits.push('0');
+    }
+    if let Some(frac) = frac_part {
+        digits.push('.');
+        digits.push_str(frac);
+    }
+    digits.parse().ok()
+}
+
+/// The decimal separator of a number made of digits, "," and ".", `None` if it has none.
+pub(crate) fn decimal_separator(s: &str) -> Option<char> {
+    let commas = s.matches(',').count();
+    let dots = s.matches('.').count();
+    match (commas, dots) {
+        (0, 0) => None,
+        // both present: the one that comes last is the decimal separator
+        (_, _) if commas > 0 && dots > 0 => {
+            let last = s.rfind(|c| c == ',' || c == '.').unwrap();
+            Some(s[last..].chars().next().unwrap())
+        }
+        (0, 1) => Some('.'),
+        (1, 0) => {
+            // "1,234" groups thousands, "1,5" is a decimal comma
+            let (head, tail) = s.split_once(',').unwrap();
+            if tail.len() == 3 && head.len() > 0 && head != "0" {
+                None
+            } else {
+                Some(',')
+            }
+        }
+        // the same separator repeated is grouping
+        _ => None,
+    }
+}
+
+/// ISO dates ("2024-03-01", "2024/03/01"), day first ("01.03.2024") and US style ("03/01/2024").
+fn parse_date(s: &str) -> Option<Value> {
+    let sep = s.chars().find(|c| matches!(c, '-' | '/' | '.'))?;
+    let parts: Vec<&str> = s.split(sep).collect();
+    if parts.len() != 3 || !parts.iter().all(|p| p.len() > 0 && p.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    let n: Vec<u32> = parts.iter().map(|p| p.parse().ok()).collect::<Option<_>>()?;
+
+    let (year, month, day) = match (parts[0].len(), parts[2].len(), sep) {
+        (4, 1..=2, '-' | '/') => (n[0], n[1], n[2]),
+        (1..=2, 4, '.' | '-') => (n[2], n[1], n[0]),
+        // month first unless that is impossible
+        (1..=2, 4, '/') if n[0] > 12 => (n[2], n[1], n[0]),
+        (1..=2, 4, '/') => (n[2], n[0], n[1]),
+        _ => return None,
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(Value::Date { year: year as i32, month, day })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(parse("42"), Some(Value::Number(42.0)));
+        assert_eq!(parse("1,234.56"), Some(Value::Number(1234.56)));
+        assert_eq!(parse("1.234,56"), Some(Value::Number(1234.56)));
+        assert_eq!(parse("1 234 567"), Some(Value::Number(1234567.0)));
+        assert_eq!(parse("1,5"), Some(Value::Number(1.5)));
+        assert_eq!(parse("0,250"), Some(Value::Number(0.25)));
+        assert_eq!(parse("(12.5)"), Some(Value::Number(-12.5)));
+        assert_eq!(parse("-3"), Some(Value::Number(-3.0)));
+        assert_eq!(parse("7-"), Some(Value::Number(-7.0)));
+        assert_eq!(parse("12."), Some(Value::Number(12.0)));
+
+        assert_eq!(parse("1,23,4"), None);
+        assert_eq!(parse("12a"), None);
+        assert_eq!(parse("-"), None);
+        assert_eq!(parse("hello"), None);
+    }
+
+    #[test]
+    fn test_currency_and_percentage() {
+        assert_eq!(parse("€ 40"), Some(Value::Currency { amount: 40.0, currency: "€".into() }));
+        assert_eq!(parse("$1,000.00"), Some(Value::Currency { amount: 1000.0, currency: "$".into() }));
+        assert_eq!(parse("-$5"), Some(Value::Currency { amount: -5.0, currency: "$".into() }));
+        assert_eq!(parse("(USD 12)"), Some(Value::Currency { amount: -12.0, currency: "USD".into() }));
+        assert_eq!(parse("12,50 €"), Some(Value::Currency { amount: 12.5, currency: "€".into() }));
+        assert_eq!(parse("12%"), Some(Value::Percentage(12.0)));
+        assert_eq!(parse("-0.5 %"), Some(Value::Percentage(-0.5)));
+        assert_eq!(parse("ISO 9001"), None);
+    }
+
+    #[test]
+    fn test_dates_and_roman() {
+        assert_eq!(parse("2024-03-01"), Some(Value::Date { year: 2024, month: 3, day: 1 }));
+        assert_eq!(parse("01.03.2024"), Some(Value::Date { year: 2024, month: 3, day: 1 }));
+        assert_eq!(parse("03/01/2024"), Some(Value::Date { year: 2024, month: 3, day: 1 }));
+        assert_eq!(parse("25/12/2024"), Some(Value::Date { year: 2024, month: 12, day: 25 }));
+        assert_eq!(parse("2024-13-01"), None);
+
+        assert_eq!(parse("XIV"), Some(Value::Roman(14)));
+        assert_eq!(parse("MCMXC"), Some(Value::Roman(1990)));
+        // words that look like roman numerals
+        for word in ["xiv", "I", "cm", "mix", "Mix", "IIII", "VX", "x"] {
+            assert_eq!(parse(word), None);
+        }
+    }
+}